@@ -2,11 +2,18 @@ BEGIN TRANSACTION;
 CREATE TABLE IF NOT EXISTS `streams` (
 	`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
 	`user_id`	TEXT,
+	`user_login`	TEXT,
 	`user_name`	TEXT,
 	`game_id`	TEXT,
 	`game_name`	TEXT,
 	`title`	TEXT,
-	`viewers`	TEXT
+	`viewers`	TEXT,
+	`peak_viewers`	TEXT,
+	`started_at`	TEXT,
+	`channel_id`	TEXT,
+	`message_id`	TEXT,
+	`updated`	TEXT,
+	`modified`	TEXT
 );
 CREATE TABLE IF NOT EXISTS `runners` (
 	`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -28,6 +35,53 @@ CREATE TABLE IF NOT EXISTS `records` (
 	`video`	TEXT,
 	`active`	TEXT,
 	`status`	TEXT,
-	`sync_status`	TEXT
+	`sync_status`	TEXT,
+	`date`	TEXT,
+	`weblink`	TEXT,
+	`src_last_seen`	TEXT,
+	`dt_last_seen`	TEXT
 );
+CREATE TABLE IF NOT EXISTS `strats` (
+	`id`	INTEGER PRIMARY KEY,
+	`name`	TEXT,
+	`category_name`	TEXT,
+	`area_name`	TEXT,
+	`room_name`	TEXT,
+	`room_id`	TEXT,
+	`description`	TEXT,
+	`difficulty`	TEXT,
+	`user_name`	TEXT,
+	`link`	TEXT
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS `strats_fts` USING fts4(`name`, `room_name`, `area_name`, `description`);
+CREATE TABLE IF NOT EXISTS `sync_state` (
+	`name`	TEXT PRIMARY KEY,
+	`last_sync`	TEXT
+);
+CREATE TABLE IF NOT EXISTS `record_runners` (
+	`record_id`	TEXT,
+	`runner_id`	TEXT
+);
+CREATE UNIQUE INDEX IF NOT EXISTS `record_runners_unique` ON `record_runners` (`record_id`, `runner_id`);
+CREATE TABLE IF NOT EXISTS `record_sources` (
+	`record_id`	TEXT,
+	`source`	TEXT,
+	`source_id`	TEXT,
+	`matched_by`	TEXT,
+	`first_seen`	TEXT,
+	`last_seen`	TEXT
+);
+CREATE UNIQUE INDEX IF NOT EXISTS `record_sources_unique` ON `record_sources` (`record_id`, `source`);
+CREATE TABLE IF NOT EXISTS `payload_hashes` (
+	`key`	TEXT PRIMARY KEY,
+	`hash`	TEXT
+);
+CREATE TABLE IF NOT EXISTS `twitch_users` (
+	`id`	TEXT PRIMARY KEY,
+	`login`	TEXT,
+	`display_name`	TEXT,
+	`data`	TEXT,
+	`updated`	TEXT
+);
+CREATE INDEX IF NOT EXISTS `twitch_users_login` ON `twitch_users` (`login`);
 COMMIT;
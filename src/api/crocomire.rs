@@ -1,6 +1,4 @@
-use reqwest;
-use serde_json;
-use std::io::{Error, ErrorKind};
+use api::http::HttpClient;
 
 #[derive(Deserialize, Debug)]
 pub struct Strategy
@@ -15,27 +13,17 @@ pub struct Strategy
     pub link: String,
     pub name: String,
     pub room_name: String,
+    #[serde(default)]
+    pub room_id: i32,
     pub user_name: String
 }
 
 impl Strategy
 {
-    pub fn find(strat: &String) -> Result<Vec<Strategy>, Box<::std::error::Error>>
+    pub fn get_all() -> Result<Vec<Strategy>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get(format!("https://crocomi.re/api/strats/{}", strat).as_str()).send()?;
-
-        let body = response.text()?;
-        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-        
-        if let Some(strats) = data.pointer("/strats")
-        {
-            if let Ok(strat_list) = serde_json::from_value::<Vec<Strategy>>(strats.clone())
-            {
-                return Ok(strat_list);
-            }
-        }
-
-        Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse crocomire data")))        
+        let http = HttpClient::for_service("CROCOMIRE", "https://crocomi.re/api");
+        let response = http.send(http.get("/strats"))?;
+        Ok(response.data("/strats")?)
     }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
-use reqwest;
 use serde_json;
+use api::http::HttpClient;
 
 #[derive(Deserialize, Debug)]
 pub struct DeerTierRecord
@@ -25,20 +25,89 @@ pub struct DeerTierRecord
 }
 
 
-pub struct DeerTier;
+pub struct DeerTier
+{
+    http: HttpClient
+}
+
 impl DeerTier
 {
     pub fn new() -> DeerTier
     {
-        DeerTier {}
+        DeerTier
+        {
+            http: HttpClient::for_service("DEERTIER", "https://deertier.com/api").cached()
+        }
+    }
+
+    /* The record list together with a hash of the response */
+    pub fn get_records_payload(&self) -> Result<(Vec<DeerTierRecord>, String), Box<::std::error::Error>>
+    {
+        let response = self.http.send(self.http.get("/records"))?;
+        Ok((response.json()?, response.hash()))
     }
 
-    pub fn get_all_records(&self) -> Result<Vec<DeerTierRecord>, Box<::std::error::Error>>
+    pub fn parse_records(body: &str) -> Result<Vec<DeerTierRecord>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get("https://deertier.com/api/records").send()?;
-        let body = response.text()?;
-        let records: Vec<DeerTierRecord> = serde_json::from_str(&body)?;
+        let records: Vec<DeerTierRecord> = serde_json::from_str(body)?;
         Ok(records)
     }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use api::http::{HttpClient, FixtureTransport};
+    use models::records::{Record, Category, Region};
+    use super::DeerTier;
+
+    fn recorded_records() -> String
+    {
+        let http = HttpClient::with_transport("DEERTIER", "https://deertier.com/api", Arc::new(FixtureTransport::new(PathBuf::from("fixtures"))));
+        http.send(http.get("/records")).expect("The DeerTier records fixture is missing").body
+    }
+
+    #[test]
+    fn parses_recorded_records()
+    {
+        let records = DeerTier::parse_records(&recorded_records()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, 1);
+        assert_eq!(records[0].username, "total");
+        assert_eq!(records[0].category, "AnyPercentRealTime");
+        assert_eq!(records[0].real_time, Some(String::from("0:41:55")));
+        assert_eq!(records[0].escape_game_time, None);
+        assert_eq!(records[1].category, "PALAnyPercentRealTime");
+    }
+
+    #[test]
+    fn rejects_malformed_records()
+    {
+        assert!(DeerTier::parse_records("{\"error\": \"Not found\"}").is_err());
+        assert!(DeerTier::parse_records("[{\"ID\": 1}]").is_err());
+    }
+
+    #[test]
+    fn converts_records_without_resolving_runners()
+    {
+        let records = DeerTier::parse_records(&recorded_records()).unwrap();
+
+        let ntsc = Record::from_deertier_record(&records[0]);
+        assert_eq!(ntsc.id, 0);
+        assert_eq!(ntsc.category, Category::from_name("any%"));
+        assert_eq!(ntsc.region, Region::NTSC);
+        assert_eq!(ntsc.realtime, 41 * 60 + 55);
+        assert_eq!(ntsc.gametime, 39 * 60);
+        assert_eq!(ntsc.date, "2018-03-01");
+        assert_eq!(ntsc.runner_id, 0);
+        assert_eq!(ntsc.credits.len(), 1);
+        assert_eq!(ntsc.credits[0].dt_id, "total");
+
+        let pal = Record::from_deertier_record(&records[1]);
+        assert_eq!(pal.category, Category::from_name("any%"));
+        assert_eq!(pal.region, Region::PAL);
+        assert_eq!(pal.runner_names(), "zoast");
+    }
 }
\ No newline at end of file
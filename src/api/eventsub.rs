@@ -0,0 +1,222 @@
+use serde_json;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use hex;
+use chrono::{DateTime, Utc};
+
+/* Twitch retries undelivered messages, anything older than this could be a replay */
+const MAX_MESSAGE_AGE: i64 = 600;
+
+// The EventSub headers of one webhook delivery
+#[derive(Debug, Clone, Default)]
+pub struct EventSubHeaders
+{
+    pub message_id: String,
+    pub message_type: String,
+    pub timestamp: String,
+    pub signature: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent
+{
+    Online(String),
+    Offline(String)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventSubReply
+{
+    /* Answer the subscription verification with the challenge */
+    Challenge(String),
+    Event(StreamEvent),
+    /* Valid message that needs no further handling */
+    Accepted,
+    Rejected(u16, &'static str)
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubMessage
+{
+    challenge: Option<String>,
+    subscription: EventSubSubscription,
+    event: Option<EventSubEvent>
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubSubscription
+{
+    #[serde(rename="type")]
+    event_type: String,
+    status: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubEvent
+{
+    broadcaster_user_id: String
+}
+
+fn mac(secret: &str, headers: &EventSubHeaders, body: &str) -> Hmac<Sha256>
+{
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.input(headers.message_id.as_bytes());
+    mac.input(headers.timestamp.as_bytes());
+    mac.input(body.as_bytes());
+    mac
+}
+
+/* The signature twitch sends for a message: sha256= followed by the hex HMAC of id, timestamp and body */
+pub fn sign(secret: &str, headers: &EventSubHeaders, body: &str) -> String
+{
+    format!("sha256={}", hex::encode(mac(secret, headers, body).result().code()))
+}
+
+pub fn verify_signature(secret: &str, headers: &EventSubHeaders, body: &str) -> bool
+{
+    if !headers.signature.starts_with("sha256=")
+    {
+        return false;
+    }
+
+    match hex::decode(&headers.signature[7..])
+    {
+        /* verify compares in constant time */
+        Ok(signature) => mac(secret, headers, body).verify(&signature).is_ok(),
+        Err(_) => false
+    }
+}
+
+/* Works out what to do with one delivery, the caller takes care of duplicate message ids */
+pub fn handle(secret: &str, headers: &EventSubHeaders, body: &str, now: DateTime<Utc>) -> EventSubReply
+{
+    if !verify_signature(secret, headers, body)
+    {
+        return EventSubReply::Rejected(403, "invalid signature");
+    }
+
+    match DateTime::parse_from_rfc3339(&headers.timestamp)
+    {
+        Ok(sent) if (now.timestamp() - sent.timestamp()).abs() <= MAX_MESSAGE_AGE => (),
+        _ => return EventSubReply::Rejected(403, "stale or missing timestamp")
+    }
+
+    let message: EventSubMessage = match serde_json::from_str(body)
+    {
+        Ok(m) => m,
+        Err(_) => return EventSubReply::Rejected(400, "malformed message")
+    };
+
+    match headers.message_type.as_str()
+    {
+        "webhook_callback_verification" => match message.challenge
+        {
+            Some(c) => EventSubReply::Challenge(c),
+            None => EventSubReply::Rejected(400, "missing challenge")
+        },
+        "notification" => match (message.subscription.event_type.as_str(), message.event)
+        {
+            ("stream.online", Some(e)) => EventSubReply::Event(StreamEvent::Online(e.broadcaster_user_id)),
+            ("stream.offline", Some(e)) => EventSubReply::Event(StreamEvent::Offline(e.broadcaster_user_id)),
+            _ => EventSubReply::Accepted
+        },
+        "revocation" =>
+        {
+            warn!("Twitch revoked the {} subscription: {}", message.subscription.event_type, message.subscription.status.unwrap_or(String::new()));
+            EventSubReply::Accepted
+        },
+        _ => EventSubReply::Rejected(400, "unknown message type")
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use chrono::{DateTime, Duration, Utc};
+    use super::{handle, sign, EventSubHeaders, EventSubReply, StreamEvent};
+
+    const SECRET: &str = "fixture-secret";
+
+    fn now() -> DateTime<Utc>
+    {
+        DateTime::parse_from_rfc3339("2020-10-11T10:11:12Z").unwrap().with_timezone(&Utc)
+    }
+
+    /* Headers for a message sent at the given time, signed the way twitch signs them */
+    fn signed(message_type: &str, body: &str, sent: DateTime<Utc>) -> EventSubHeaders
+    {
+        let mut headers = EventSubHeaders
+        {
+            message_id: String::from("befa7b53-d79d-478f-86b9-120f112b044e"),
+            message_type: String::from(message_type),
+            timestamp: sent.to_rfc3339(),
+            signature: String::new()
+        };
+        headers.signature = sign(SECRET, &headers, body);
+        headers
+    }
+
+    #[test]
+    fn accepts_signed_notifications()
+    {
+        let online = include_str!("../../fixtures/eventsub/stream_online.json");
+        assert_eq!(handle(SECRET, &signed("notification", online, now()), online, now()), EventSubReply::Event(StreamEvent::Online(String::from("12826"))));
+
+        let offline = include_str!("../../fixtures/eventsub/stream_offline.json");
+        assert_eq!(handle(SECRET, &signed("notification", offline, now()), offline, now()), EventSubReply::Event(StreamEvent::Offline(String::from("12826"))));
+    }
+
+    #[test]
+    fn rejects_tampered_bodies()
+    {
+        let body = include_str!("../../fixtures/eventsub/stream_online.json");
+        let headers = signed("notification", body, now());
+        let tampered = body.replace("12826", "12827");
+        assert_eq!(handle(SECRET, &headers, &tampered, now()), EventSubReply::Rejected(403, "invalid signature"));
+        assert_eq!(handle("another-secret", &headers, body, now()), EventSubReply::Rejected(403, "invalid signature"));
+    }
+
+    #[test]
+    fn rejects_malformed_signatures()
+    {
+        let body = include_str!("../../fixtures/eventsub/stream_online.json");
+        let mut headers = signed("notification", body, now());
+        let valid = headers.signature.clone();
+
+        headers.signature = format!("sha256={}", "zz".repeat(32));
+        assert_eq!(handle(SECRET, &headers, body, now()), EventSubReply::Rejected(403, "invalid signature"));
+
+        headers.signature = String::from(&valid[7..]);
+        assert_eq!(handle(SECRET, &headers, body, now()), EventSubReply::Rejected(403, "invalid signature"));
+
+        headers.signature = String::new();
+        assert_eq!(handle(SECRET, &headers, body, now()), EventSubReply::Rejected(403, "invalid signature"));
+    }
+
+    #[test]
+    fn rejects_stale_timestamps()
+    {
+        let body = include_str!("../../fixtures/eventsub/stream_online.json");
+        let stale = signed("notification", body, now() - Duration::minutes(11));
+        assert_eq!(handle(SECRET, &stale, body, now()), EventSubReply::Rejected(403, "stale or missing timestamp"));
+
+        let recent = signed("notification", body, now() - Duration::minutes(9));
+        assert_eq!(handle(SECRET, &recent, body, now()), EventSubReply::Event(StreamEvent::Online(String::from("12826"))));
+    }
+
+    #[test]
+    fn answers_the_verification_challenge()
+    {
+        let body = include_str!("../../fixtures/eventsub/verification.json");
+        let headers = signed("webhook_callback_verification", body, now());
+        assert_eq!(handle(SECRET, &headers, body, now()), EventSubReply::Challenge(String::from("pogchamp-kappa-360noscope-vohiyo")));
+    }
+
+    #[test]
+    fn accepts_revocations()
+    {
+        let body = include_str!("../../fixtures/eventsub/revocation.json");
+        let headers = signed("revocation", body, now());
+        assert_eq!(handle(SECRET, &headers, body, now()), EventSubReply::Accepted);
+    }
+}
\ No newline at end of file
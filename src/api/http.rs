@@ -0,0 +1,646 @@
+use reqwest;
+use serde_json;
+use serde::de::DeserializeOwned;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use hex;
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/* The longest wait between retries in seconds, whether it comes from the backoff or a Retry-After */
+const MAX_RETRY_DELAY: u64 = 300;
+
+// Errors for responses that were received but could not be used
+#[derive(Debug)]
+pub enum ApiError
+{
+    /* The service answered with a non-success status */
+    Status(u16, String),
+    /* The body was not the JSON that was expected */
+    Decode(String, serde_json::Error),
+    /* The JSON was valid but did not contain the expected field */
+    Missing(String, String)
+}
+
+impl fmt::Display for ApiError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            ApiError::Status(status, ref url) => write!(f, "{} answered with status {}", url, status),
+            ApiError::Decode(ref url, ref why) => write!(f, "Could not decode the response from {}: {}", url, why),
+            ApiError::Missing(ref url, ref pointer) => write!(f, "The response from {} has no {}", url, pointer)
+        }
+    }
+}
+
+impl ::std::error::Error for ApiError
+{
+    fn description(&self) -> &str
+    {
+        match *self
+        {
+            ApiError::Status(_, _) => "unexpected status",
+            ApiError::Decode(_, _) => "could not decode response",
+            ApiError::Missing(_, _) => "missing field in response"
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Method
+{
+    Get,
+    Post
+}
+
+#[derive(Debug, Clone)]
+pub struct Request
+{
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>
+}
+
+impl Request
+{
+    pub fn header(mut self, name: &str, value: &str) -> Request
+    {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    pub fn body(mut self, body: String) -> Request
+    {
+        self.body = Some(body);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Response
+{
+    #[serde(skip)]
+    pub url: String,
+    pub status: u16,
+    /* Header names are lowercase */
+    pub headers: HashMap<String, String>,
+    pub body: String
+}
+
+impl Response
+{
+    pub fn is_success(&self) -> bool
+    {
+        self.status >= 200 && self.status < 300
+    }
+
+    pub fn header(&self, name: &str) -> Option<&String>
+    {
+        self.headers.get(&name.to_lowercase())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ApiError>
+    {
+        if !self.is_success()
+        {
+            return Err(ApiError::Status(self.status, self.url.clone()));
+        }
+        serde_json::from_str(&self.body).map_err(|why| ApiError::Decode(self.url.clone(), why))
+    }
+
+    /* Hash of the body, to tell whether a payload changed since it was last processed.
+       It is stored, so it has to stay the same across builds. */
+    pub fn hash(&self) -> String
+    {
+        hex::encode(Sha256::digest(self.body.as_bytes()))
+    }
+
+    /* Decodes the part of the body at a JSON pointer, most APIs wrap their payload in /data */
+    pub fn data<T: DeserializeOwned>(&self, pointer: &str) -> Result<T, ApiError>
+    {
+        let value: serde_json::Value = self.json()?;
+        match value.pointer(pointer)
+        {
+            Some(v) => serde_json::from_value(v.clone()).map_err(|why| ApiError::Decode(self.url.clone(), why)),
+            None => Err(ApiError::Missing(self.url.clone(), String::from(pointer)))
+        }
+    }
+}
+
+// Sends requests somewhere, the network by default
+pub trait Transport: Send + Sync
+{
+    fn send(&self, request: &Request) -> Result<Response, Box<::std::error::Error>>;
+}
+
+pub struct ReqwestTransport
+{
+    client: reqwest::Client
+}
+
+impl ReqwestTransport
+{
+    /* Requests that take longer than HTTP_TIMEOUT seconds are abandoned */
+    pub fn new() -> ReqwestTransport
+    {
+        let timeout: u64 = env::var("HTTP_TIMEOUT").ok().and_then(|t| t.parse().ok()).unwrap_or(30);
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(timeout)).build()
+        {
+            Ok(c) => c,
+            Err(why) =>
+            {
+                warn!("Could not set up the HTTP client with a timeout: {:?}", why);
+                reqwest::Client::new()
+            }
+        };
+
+        ReqwestTransport
+        {
+            client: client
+        }
+    }
+}
+
+impl Transport for ReqwestTransport
+{
+    fn send(&self, request: &Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let mut headers = reqwest::header::Headers::new();
+        for &(ref name, ref value) in request.headers.iter()
+        {
+            headers.set_raw(name.clone(), value.clone());
+        }
+
+        let mut builder = match request.method
+        {
+            Method::Get => self.client.get(request.url.as_str()),
+            Method::Post => self.client.post(request.url.as_str())
+        };
+        builder.headers(headers);
+        if let Some(ref body) = request.body
+        {
+            builder.body(body.clone());
+        }
+        let mut response = builder.send()?;
+
+        Ok(Response
+        {
+            url: request.url.clone(),
+            status: response.status().as_u16(),
+            headers: response.headers().iter().map(|h| (h.name().to_lowercase(), h.value_string())).collect(),
+            body: response.text()?
+        })
+    }
+}
+
+/* Replays responses from a directory of recorded fixtures instead of touching the network */
+pub struct FixtureTransport
+{
+    dir: PathBuf
+}
+
+impl FixtureTransport
+{
+    pub fn new(dir: PathBuf) -> FixtureTransport
+    {
+        FixtureTransport
+        {
+            dir: dir
+        }
+    }
+
+    /* The file a request is recorded in, credentials in the query string are left out of the name */
+    pub fn path(&self, request: &Request) -> PathBuf
+    {
+        let url = request.url.splitn(2, "://").last().unwrap_or("");
+        let mut parts = url.splitn(2, '?');
+        let mut name = String::from(parts.next().unwrap_or(""));
+        if let Some(query) = parts.next()
+        {
+            let params: Vec<&str> = query.split('&').filter(|p| !p.starts_with("client_") && !p.contains("token")).collect();
+            name.push('?');
+            name.push_str(&params.join("&"));
+        }
+
+        let method = match request.method { Method::Get => "GET", Method::Post => "POST" };
+        let file: String = format!("{} {}", method, name).chars().map(|c| if c.is_alphanumeric() || c == '.' || c == '-' || c == '=' { c } else { '_' }).collect();
+        self.dir.join(format!("{}.json", file))
+    }
+}
+
+impl Transport for FixtureTransport
+{
+    fn send(&self, request: &Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let path = self.path(request);
+        let mut body = String::new();
+        match File::open(&path)
+        {
+            Ok(mut f) => { f.read_to_string(&mut body)?; },
+            Err(_) => return Err(Box::new(Error::new(ErrorKind::Other, format!("No recorded response for {} (expected {})", request.url, path.display()))))
+        }
+
+        let mut response: Response = serde_json::from_str(&body)?;
+        response.url = request.url.clone();
+        Ok(response)
+    }
+}
+
+/* Passes requests on to the network and writes every response to the fixture directory */
+pub struct RecordingTransport
+{
+    inner: ReqwestTransport,
+    fixtures: FixtureTransport
+}
+
+impl Transport for RecordingTransport
+{
+    fn send(&self, request: &Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let response = self.inner.send(request)?;
+        let path = self.fixtures.path(request);
+        fs::create_dir_all(&self.fixtures.dir)?;
+        File::create(&path)?.write_all(serde_json::to_string_pretty(&RecordingTransport::redact(&response))?.as_bytes())?;
+        Ok(response)
+    }
+}
+
+impl RecordingTransport
+{
+    /* Fixtures get committed, so tokens handed out by an auth service are replaced before writing */
+    fn redact(response: &Response) -> Response
+    {
+        let mut recorded = response.clone();
+        let mut value: serde_json::Value = match serde_json::from_str(&response.body)
+        {
+            Ok(v) => v,
+            Err(_) => return recorded
+        };
+
+        let mut redacted = false;
+        if let Some(object) = value.as_object_mut()
+        {
+            for key in ["access_token", "refresh_token"].iter()
+            {
+                if let Some(token) = object.get_mut(*key)
+                {
+                    *token = json!("redacted");
+                    redacted = true;
+                }
+            }
+        }
+
+        if redacted
+        {
+            recorded.body = value.to_string();
+        }
+        recorded
+    }
+}
+
+lazy_static!
+{
+    /* HTTP_FIXTURES replays recorded responses, together with HTTP_RECORD=1 it records them from the network instead */
+    static ref TRANSPORT: Arc<Transport> = match env::var("HTTP_FIXTURES")
+    {
+        Ok(ref dir) if dir != "" =>
+        {
+            let fixtures = FixtureTransport::new(PathBuf::from(dir));
+            if env::var("HTTP_RECORD").map(|r| r == "1").unwrap_or(false)
+            {
+                info!("Recording HTTP responses to {}", dir);
+                Arc::new(RecordingTransport { inner: ReqwestTransport::new(), fixtures: fixtures }) as Arc<Transport>
+            } else {
+                info!("Replaying HTTP responses from {}", dir);
+                Arc::new(fixtures) as Arc<Transport>
+            }
+        },
+        _ => Arc::new(ReqwestTransport::new()) as Arc<Transport>
+    };
+
+    /* One limiter per service, shared by every client of that service */
+    static ref LIMITERS: Mutex<HashMap<String, Arc<RateLimiter>>> = Mutex::new(HashMap::new());
+
+    /* Cache hits and misses per service since they were last taken */
+    static ref CACHE_STATS: Mutex<HashMap<String, (usize, usize)>> = Mutex::new(HashMap::new());
+}
+
+/* Returns the cache hits and misses of a service since the last call and resets them */
+pub fn take_cache_stats(service: &str) -> (usize, usize)
+{
+    CACHE_STATS.lock().unwrap().remove(service).unwrap_or((0, 0))
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry
+{
+    stored_at: i64,
+    response: Response
+}
+
+// Keeps successful GET responses on disk, keyed by URL
+pub struct ResponseCache
+{
+    dir: PathBuf,
+    ttl: i64
+}
+
+impl ResponseCache
+{
+    pub fn new(dir: PathBuf, ttl: i64) -> ResponseCache
+    {
+        ResponseCache
+        {
+            dir: dir,
+            ttl: ttl
+        }
+    }
+
+    fn path(&self, url: &str) -> PathBuf
+    {
+        self.dir.join(format!("{}.json", hex::encode(Sha256::digest(url.as_bytes()))))
+    }
+
+    fn load(&self, url: &str) -> Option<CacheEntry>
+    {
+        let mut body = String::new();
+        File::open(self.path(url)).ok()?.read_to_string(&mut body).ok()?;
+        let mut entry: CacheEntry = serde_json::from_str(&body).ok()?;
+        entry.response.url = String::from(url);
+        Some(entry)
+    }
+
+    fn store(&self, entry: &CacheEntry)
+    {
+        let path = self.path(&entry.response.url);
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| File::create(&path))
+            .and_then(|mut f| f.write_all(serde_json::to_string(entry).unwrap_or(String::new()).as_bytes()));
+        if let Err(why) = result
+        {
+            warn!("Could not cache the response from {}: {:?}", entry.response.url, why);
+        }
+    }
+}
+
+// Spaces requests to a service evenly so they stay under its request budget
+pub struct RateLimiter
+{
+    interval: Duration,
+    next: Mutex<Instant>
+}
+
+impl RateLimiter
+{
+    pub fn per_minute(requests: u32) -> RateLimiter
+    {
+        RateLimiter
+        {
+            interval: Duration::from_millis(60000 / cmp::max(requests, 1) as u64),
+            next: Mutex::new(Instant::now())
+        }
+    }
+
+    /* Blocks until the next request may be sent */
+    pub fn wait(&self)
+    {
+        let mut next = self.next.lock().unwrap();
+        let now = Instant::now();
+        if *next > now
+        {
+            thread::sleep(*next - now);
+        }
+        *next = cmp::max(now, *next) + self.interval;
+    }
+}
+
+// A client for one service, requests are made relative to its base URL
+#[derive(Clone)]
+pub struct HttpClient
+{
+    service: String,
+    base_url: String,
+    transport: Arc<Transport>,
+    limiter: Option<Arc<RateLimiter>>,
+    cache: Option<Arc<ResponseCache>>
+}
+
+impl HttpClient
+{
+    /* The base URL can be overridden with <SERVICE>_BASE_URL, e.g. SRC_BASE_URL */
+    pub fn for_service(service: &str, default_url: &str) -> HttpClient
+    {
+        let base_url = env::var(format!("{}_BASE_URL", service)).unwrap_or(String::from(default_url));
+        HttpClient::with_transport(service, &base_url, TRANSPORT.clone())
+    }
+
+    pub fn with_transport(service: &str, base_url: &str, transport: Arc<Transport>) -> HttpClient
+    {
+        HttpClient
+        {
+            service: String::from(service),
+            base_url: String::from(base_url.trim_end_matches('/')),
+            transport: transport,
+            limiter: None,
+            cache: None
+        }
+    }
+
+    /* Caches GET responses in HTTP_CACHE_DIR for HTTP_CACHE_TTL seconds, stale entries are revalidated
+       with If-None-Match/If-Modified-Since when the server sent an ETag or Last-Modified */
+    pub fn cached(mut self) -> HttpClient
+    {
+        let dir = env::var("HTTP_CACHE_DIR").unwrap_or(String::from("cache"));
+        let ttl: i64 = env::var("HTTP_CACHE_TTL").ok().and_then(|t| t.parse().ok()).unwrap_or(300);
+        self.cache = Some(Arc::new(ResponseCache::new(PathBuf::from(dir), ttl)));
+        self
+    }
+
+    /* Limits the requests to the service per minute, <SERVICE>_RATE_LIMIT overrides the default */
+    pub fn rate_limited(mut self, default_per_minute: u32) -> HttpClient
+    {
+        let per_minute: u32 = env::var(format!("{}_RATE_LIMIT", self.service)).ok().and_then(|r| r.parse().ok()).unwrap_or(default_per_minute);
+        let mut limiters = LIMITERS.lock().unwrap();
+        let limiter = limiters.entry(self.service.clone()).or_insert_with(|| Arc::new(RateLimiter::per_minute(per_minute))).clone();
+        self.limiter = Some(limiter);
+        self
+    }
+
+    pub fn get(&self, path: &str) -> Request
+    {
+        self.request(Method::Get, path)
+    }
+
+    pub fn post(&self, path: &str) -> Request
+    {
+        self.request(Method::Post, path)
+    }
+
+    fn request(&self, method: Method, path: &str) -> Request
+    {
+        Request
+        {
+            method: method,
+            url: format!("{}{}", self.base_url, path),
+            headers: Vec::new(),
+            body: None
+        }
+    }
+
+    pub fn send(&self, request: Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        match self.cache
+        {
+            Some(ref cache) if request.method == Method::Get => self.send_cached(cache, request),
+            _ => self.send_uncached(request)
+        }
+    }
+
+    fn send_cached(&self, cache: &ResponseCache, mut request: Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let now = Utc::now().timestamp();
+        let entry = cache.load(&request.url);
+        if let Some(ref e) = entry
+        {
+            if now - e.stored_at < cache.ttl
+            {
+                self.count_cache(true);
+                return Ok(e.response.clone());
+            }
+
+            if let Some(etag) = e.response.header("ETag")
+            {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(modified) = e.response.header("Last-Modified")
+            {
+                request = request.header("If-Modified-Since", modified);
+            }
+        }
+
+        let response = self.send_uncached(request)?;
+        match entry
+        {
+            Some(mut e) if response.status == 304 =>
+            {
+                e.stored_at = now;
+                cache.store(&e);
+                self.count_cache(true);
+                Ok(e.response)
+            },
+            _ =>
+            {
+                if response.is_success()
+                {
+                    cache.store(&CacheEntry { stored_at: now, response: response.clone() });
+                }
+                self.count_cache(false);
+                Ok(response)
+            }
+        }
+    }
+
+    fn count_cache(&self, hit: bool)
+    {
+        let mut stats = CACHE_STATS.lock().unwrap();
+        let entry = stats.entry(self.service.clone()).or_insert((0, 0));
+        if hit { entry.0 += 1; } else { entry.1 += 1; }
+    }
+
+    /* Sends the request, retrying network errors, 5xx and 429 responses with exponential backoff.
+       A Retry-After given in seconds is honoured instead of the backoff. */
+    fn send_uncached(&self, request: Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let max_retries: u32 = env::var("HTTP_MAX_RETRIES").ok().and_then(|r| r.parse().ok()).unwrap_or(3);
+        let base_delay: u64 = env::var("HTTP_RETRY_BASE_MS").ok().and_then(|d| d.parse().ok()).unwrap_or(500);
+
+        let mut attempt = 0;
+        loop
+        {
+            if let Some(ref limiter) = self.limiter
+            {
+                limiter.wait();
+            }
+
+            debug!("{:?} {}", request.method, request.url);
+            let factor = 2u64.checked_pow(attempt).unwrap_or(::std::u64::MAX);
+            let backoff = Duration::from_millis(cmp::min(base_delay.saturating_mul(factor), MAX_RETRY_DELAY * 1000));
+            let delay = match self.transport.send(&request)
+            {
+                Ok(response) =>
+                {
+                    if (response.status != 429 && response.status < 500) || attempt >= max_retries
+                    {
+                        return Ok(response);
+                    }
+
+                    let retry_after = response.header("Retry-After").and_then(|r| r.trim().parse::<u64>().ok());
+                    warn!("{} answered with status {}, retrying", request.url, response.status);
+                    retry_after.map(|s| Duration::from_secs(cmp::min(s, MAX_RETRY_DELAY))).unwrap_or(backoff)
+                },
+                Err(why) =>
+                {
+                    /* Only failures on the network are worth retrying, a missing fixture will stay missing */
+                    if why.downcast_ref::<reqwest::Error>().is_none() || attempt >= max_retries
+                    {
+                        return Err(why);
+                    }
+
+                    warn!("Request to {} failed, retrying: {}", request.url, why);
+                    backoff
+                }
+            };
+
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::HashMap;
+    use super::{RecordingTransport, Response};
+
+    fn response(body: &str) -> Response
+    {
+        Response { url: String::from("https://id.twitch.tv/oauth2/token"), status: 200, headers: HashMap::new(), body: String::from(body) }
+    }
+
+    #[test]
+    fn payload_hashes_are_stable()
+    {
+        assert_eq!(response("abc").hash(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn recordings_leave_out_tokens()
+    {
+        let recorded = RecordingTransport::redact(&response("{\"access_token\": \"secret1\", \"refresh_token\": \"secret2\", \"expires_in\": 3600}"));
+        assert!(!recorded.body.contains("secret"));
+
+        let value: ::serde_json::Value = ::serde_json::from_str(&recorded.body).unwrap();
+        assert_eq!(value["access_token"], "redacted");
+        assert_eq!(value["expires_in"], 3600);
+    }
+
+    #[test]
+    fn recordings_keep_other_bodies()
+    {
+        let body = "{\"data\": [{\"id\": \"12826\"}]}";
+        assert_eq!(RecordingTransport::redact(&response(body)).body, body);
+        assert_eq!(RecordingTransport::redact(&response("not json")).body, "not json");
+    }
+}
\ No newline at end of file
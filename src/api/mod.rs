@@ -2,3 +2,5 @@ pub mod deertier;
 pub mod src;
 pub mod twitch;
 pub mod crocomire;
+pub mod http;
+pub mod eventsub;
\ No newline at end of file
@@ -1,7 +1,9 @@
-use reqwest;
-use serde_json;
-use std::io::{Error, ErrorKind};
-use chrono::{DateTime, Utc};
+use api::http::{HttpClient, Response};
+use std::collections::{BTreeMap, HashMap};
+use std::cmp;
+use std::env;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use models::records;
 use models::records::{Category, Region};
 
@@ -12,7 +14,12 @@ pub struct SrcLeaderboard
     pub game: String,
     pub category: String,
     pub runs: Vec<SrcLeaderboardRuns>,
-    pub players: SrcLeaderboardPlayers
+    pub players: SrcLeaderboardPlayers,
+    /* Where the board was fetched from and a hash of the response, to skip boards that did not change */
+    #[serde(skip)]
+    pub source_url: String,
+    #[serde(skip)]
+    pub payload_hash: String
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -42,20 +49,7 @@ pub struct SrcRun
     pub times: SrcTimes,
     pub system: Option<SrcSystem>,
     pub splits: Option<SrcSplits>,
-    pub values: Option<SrcValue>
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-pub struct SrcValue
-{
-    #[serde(rename="onv6jzw8")]
-    pub low_percent: Option<String>,
-    #[serde(rename="kn02d083")]
-    pub gt_code: Option<String>,
-    #[serde(rename="wl360wwl")]
-    pub miniboss: Option<String>,
-    #[serde(rename="wle6dpr8")]
-    pub region: Option<String>
+    pub values: Option<HashMap<String, String>>
 }
 
 impl SrcRun
@@ -144,100 +138,224 @@ pub struct SrcCategory
     pub weblink: String,
     #[serde(rename="type")]
     pub category_type: String,
-    pub rules: String,
+    pub rules: Option<String>,
+    pub variables: Option<SrcVariables>
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SrcVariables
+{
+    pub data: Vec<SrcVariable>
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SrcVariable
+{
+    pub id: String,
+    pub name: String,
+    #[serde(rename="is-subcategory")]
+    pub is_subcategory: bool,
+    pub values: SrcVariableValues
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SrcVariableValues
+{
+    pub values: BTreeMap<String, SrcVariableValue>
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SrcVariableValue
+{
+    pub label: String
+}
+
+/* One leaderboard: a category together with one value for each of its subcategory variables */
+#[derive(Debug, Clone)]
+pub struct SrcBoardQuery
+{
+    pub category: String,
+    pub variables: BTreeMap<String, String>,
+    pub label: String
+}
+
+impl SrcBoardQuery
+{
+    pub fn path(&self, game_id: &str) -> String
+    {
+        let variables: String = self.variables.iter().map(|(k, v)| format!("var-{}={}&", k, v)).collect();
+        format!("/leaderboards/{}/category/{}?{}embed=players", game_id, self.category, variables)
+    }
+
+    pub fn mapping(&self) -> (Category, Region)
+    {
+        let values: HashMap<String, String> = self.variables.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        Category::from_src(&self.category, &values)
+    }
+}
+
+// The leaderboards that could be fetched, the error for each one that could not, and the ones no category maps onto
+pub struct SrcBoards
+{
+    pub boards: Vec<SrcLeaderboard>,
+    pub errors: Vec<(SrcBoardQuery, String)>,
+    pub unmapped: Vec<SrcBoardQuery>
 }
 
 pub struct Src
 {
-    api_key: String
+    http: HttpClient
 }
 
 impl Src
 {
-    pub fn new(api_key: String) -> Src
+    pub fn new() -> Src
     {
         Src
         {
-            api_key: api_key
+            http: HttpClient::for_service("SRC", "https://www.speedrun.com/api/v1").rate_limited(90).cached()
         }
     }
 
-    pub fn get_run(&self) -> Result<SrcRun, Box<::std::error::Error>>
+    pub fn get_run(&self, run_id: &String) -> Result<SrcRun, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get("https://www.speedrun.com/api/v1/runs/yo4dnx1m").send()?;
-        let body = response.text()?;
-
-        let data: serde_json::Value = serde_json::from_str(&body)?;
-        if let Some(run) = data.pointer("/data")
-        {
-            let src_run: SrcRun = serde_json::from_value(run.clone()).unwrap();
-            Ok(src_run)
-        } 
-        else
-        {
-            Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse src run data")))
-        }
+        let response = self.http.send(self.http.get(&format!("/runs/{}", run_id)))?;
+        Ok(response.data("/data")?)
     }
 
     pub fn get_categories(&self, game_id: String) -> Result<Vec<SrcCategory>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let url = format!("https://www.speedrun.com/api/v1/games/{}/categories", game_id);
-        let mut response = reqclient.get(url.as_str()).send()?;
-        let body = response.text()?;
-        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-        
-        if let Some(categories) = data.pointer("/data")
+        let response = self.http.send(self.http.get(&format!("/games/{}/categories?embed=variables", game_id)))?;
+        Ok(response.data("/data")?)
+    }
+
+    /* Enumerates every full-game category and each combination of its subcategory variable values */
+    pub fn get_board_queries(&self, game_id: String) -> Result<Vec<SrcBoardQuery>, Box<::std::error::Error>>
+    {
+        let mut queries: Vec<SrcBoardQuery> = Vec::new();
+        for c in self.get_categories(game_id)?
         {
-            if let Ok(src_categories) = serde_json::from_value::<Vec<SrcCategory>>(categories.clone())
+            if c.category_type != "per-game"
+            {
+                continue;
+            }
+
+            let mut combinations = vec![SrcBoardQuery { category: c.id.clone(), variables: BTreeMap::new(), label: c.name.clone() }];
+            let variables = c.variables.as_ref().map(|v| v.data.iter().filter(|v| v.is_subcategory).collect::<Vec<_>>()).unwrap_or(Vec::new());
+            for v in variables
             {
-                return Ok(src_categories);
+                let mut expanded: Vec<SrcBoardQuery> = Vec::new();
+                for q in combinations.iter()
+                {
+                    for (value_id, value) in v.values.values.iter()
+                    {
+                        let mut query = q.clone();
+                        query.variables.insert(v.id.clone(), value_id.clone());
+                        query.label = format!("{} - {}", query.label, value.label);
+                        expanded.push(query);
+                    }
+                }
+                combinations = expanded;
             }
+            queries.extend(combinations);
         }
-        Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse src category data")))
+        Ok(queries)
     }
 
-    pub fn get_leaderboard(&self, game_id: String) -> Result<Vec<SrcLeaderboard>, Box<::std::error::Error>>
+    /* Fetches every mapped leaderboard, SRC_FETCH_CONCURRENCY at a time under the shared rate limit.
+       A board that fails is reported on its own instead of discarding the others. */
+    pub fn get_leaderboard(&self, game_id: String) -> Result<SrcBoards, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut leaderboard: Vec<SrcLeaderboard> = Vec::new();
-        let categories = self.get_category_urls();
+        let mut queries: Vec<(usize, SrcBoardQuery)> = Vec::new();
+        let mut unmapped: Vec<SrcBoardQuery> = Vec::new();
+        for q in self.get_board_queries(game_id.clone())?
+        {
+            /* Boards that don't map onto a defined category would otherwise be imported as Unknown */
+            if q.mapping().0 == Category::Unknown
+            {
+                warn!("No category is defined for the SRC leaderboard '{}' ({} {:?}), skipping it", q.label, q.category, q.variables);
+                unmapped.push(q);
+                continue;
+            }
+            queries.push((queries.len(), q));
+        }
+
+        let concurrency: usize = env::var("SRC_FETCH_CONCURRENCY").ok().and_then(|c| c.parse().ok()).unwrap_or(4);
+        let workers = cmp::max(cmp::min(concurrency, queries.len()), 1);
+        let queue = Arc::new(Mutex::new(queries));
+        let (tx, rx) = mpsc::channel();
 
-        for c in categories
+        for _ in 0..workers
         {
-            let url = format!("https://www.speedrun.com/api/v1/leaderboards/{}/category/{}", &game_id, &c);
-            let mut response = reqclient.get(url.as_str()).send()?;
-            let body = response.text()?;
-            let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-    
-            if let Some(runs) = data.pointer("/data")
+            let queue = queue.clone();
+            let tx = tx.clone();
+            let http = self.http.clone();
+            let game_id = game_id.clone();
+            thread::spawn(move ||
             {
-                if let Ok(src_board) = serde_json::from_value::<SrcLeaderboard>(runs.clone())
+                loop
                 {
-                    leaderboard.push(src_board);
+                    let next = queue.lock().unwrap().pop();
+                    let (index, q) = match next
+                    {
+                        Some(n) => n,
+                        None => break
+                    };
+                    let result = Src::fetch_board(&http, &game_id, &q).map_err(|why| why.to_string());
+                    if tx.send((index, q, result)).is_err()
+                    {
+                        break;
+                    }
                 }
-                else
+            });
+        }
+        drop(tx);
+
+        /* Keep the boards in the order SRC lists the categories */
+        let mut results: Vec<(usize, SrcBoardQuery, Result<SrcLeaderboard, String>)> = rx.iter().collect();
+        results.sort_by_key(|r| r.0);
+
+        let mut boards = SrcBoards { boards: Vec::new(), errors: Vec::new(), unmapped: unmapped };
+        for (_, q, result) in results
+        {
+            match result
+            {
+                Ok(board) => boards.boards.push(board),
+                Err(why) =>
                 {
-                    return Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse src leaderboard data")));    
+                    warn!("Could not fetch the SRC leaderboard '{}': {}", q.label, why);
+                    boards.errors.push((q, why));
                 }
-            } 
-            else 
-            {
-                return Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse src leaderboard data")));
             }
         }
 
-        Ok(leaderboard)
+        Ok(boards)
     }
 
-    pub fn get_all_records(&self, game_id: String) -> Result<Vec<records::Record>, Box<::std::error::Error>>
+    fn fetch_board(http: &HttpClient, game_id: &str, q: &SrcBoardQuery) -> Result<SrcLeaderboard, Box<::std::error::Error>>
+    {
+        let response = http.send(http.get(&q.path(game_id)))?;
+        let mut board: SrcLeaderboard = response.data("/data")?;
+        board.source_url = response.url.clone();
+        board.payload_hash = response.hash();
+        Ok(board)
+    }
+
+    /* Parses a leaderboard response, fetched with the players embedded */
+    pub fn parse_leaderboard(body: &str) -> Result<SrcLeaderboard, Box<::std::error::Error>>
+    {
+        let response = Response { url: String::from("leaderboard dump"), status: 200, headers: HashMap::new(), body: String::from(body) };
+        Ok(response.data("/data")?)
+    }
+
+    /* Turns fetched or dumped leaderboards into records, the runners are only looked up */
+    pub fn to_records(leaderboards: Vec<SrcLeaderboard>) -> Vec<records::Record>
     {
-        let leaderboards = self.get_leaderboard(game_id.clone())?;
         let mut records: Vec<records::Record> = Vec::new();
         
         for lb in leaderboards
         {
+            let users = lb.players.data;
             for lr in lb.runs
             {
                 let run = lr.run;
@@ -249,169 +367,159 @@ impl Src
                     continue;
                 }
 
-                if let Some(player) = run.players.first()
+                let (category, region) = match (run.category.as_ref(), run.values.as_ref())
                 {
-                    if let Some(user) =
-                        match player.rel.as_str()
-                        {
-                            "user" => lb.players.data.iter().find(|&x| x.id.is_some() && x.id.as_ref().unwrap() == player.id.as_ref().unwrap_or(&String::from(""))),
-                            _ => lb.players.data.iter().find(|&x| x.name.is_some() && x.name.as_ref().unwrap() == player.name.as_ref().unwrap_or(&String::from("")))
-                        }
-                    {
-                        let runner = match user.rel.as_str()
-                        {
-                            "user" => match records::Runner::from_src_id(user.id.as_ref().unwrap())
-                            {
-                                Ok(r) => r,
-                                _ => match records::Runner::from_name(&user.names.as_ref().unwrap().international)
-                                {
-                                    Ok(mut rr) => {                                
-                                        rr.src_id = user.id.as_ref().unwrap().clone();
-                                        rr.save();
-                                        rr
-                                    },
-                                    _ =>
-                                    {
-                                        let mut new_runner = records::Runner::new(user.names.as_ref().unwrap().international.clone(), String::from(""), user.id.as_ref().unwrap().clone(), 1);
-                                        new_runner.save();
-                                        new_runner
-                                    }
-                                }
-                            }
-                            _ => 
-                            {
-                                match records::Runner::from_name(&user.name.as_ref().unwrap())
-                                {
-                                    Ok(r) => r,
-                                    _ =>
-                                    {
-                                        let mut new_runner = records::Runner::new(user.name.as_ref().unwrap().clone(), String::from(""), String::from(""), 1);
-                                        new_runner.save();
-                                        new_runner                              
-                                    }
-                                }
-                            }
-                        };
-
-                        let record = records::Record
-                        {
-                            id: 0,
-                            dt_id: 0,
-                            src_id: run.id,
-                            runner_id: runner.id,
-                            category: if run.category.is_some() && run.values.is_some() { self.map_category(&run.category.as_ref().unwrap(), &run.values.as_ref().unwrap()) } else { Category::Unknown },
-                            region: if run.category.is_some() && run.values.is_some() { self.map_region(&run.category.as_ref().unwrap(), &run.values.as_ref().unwrap()) } else { Region::NTSC },
-                            realtime: run.times.realtime_t.unwrap_or(0.0) as i32,
-                            gametime: run.times.ingame_t.unwrap_or(0.0) as i32,
-                            comment: run.comment.unwrap_or(String::from("")),
-                            video: match run.videos
-                            {
-                                Some(v) => if let Some(links) = v.links { if let Some(video) = links.first() { video.uri.clone().unwrap_or(String::from("")) } else { String::from("") }} else { String::from("") },
-                                _ => String::from("")
-                            },
-                            active: 1            
-                        };
-
-                        records.push(record);
-                    }
+                    (Some(c), Some(v)) => Category::from_src(c, v),
+                    _ => (Category::Unknown, Region::NTSC)
+                };
+
+                if category == Category::Unknown
+                {
+                    warn!("SRC run {} does not map onto a defined category, skipping it", run.id);
+                    continue;
                 }
-            }
-        }
 
-        Ok(records)
-    }
+                /* Co-op and race runs credit every player, guests are matched by name */
+                let runners: Vec<records::RunnerRef> = run.players.iter().filter_map(|p| Src::resolve_player(p, &users)).collect();
+                if runners.len() == 0
+                {
+                    warn!("Could not resolve any players for SRC run {}, skipping it", run.id);
+                    continue;
+                }
 
-    fn map_region(&self, category_id: &String, values: &SrcValue) -> Region
-    {
-        match self.map_category(category_id, values)
-        {
-            Category::LowPercentIceBoots => Region::PAL,
-            Category::LowPercentSpeedBoots => Region::PAL,
-            _ => match values.region.as_ref().unwrap().as_str()
+                let record = records::Record
+                {
+                    id: 0,
+                    dt_id: 0,
+                    src_id: run.id,
+                    runner_id: runners[0].id,
+                    runners: runners.iter().map(|r| r.id).collect(),
+                    category: category,
+                    region: region,
+                    realtime: run.times.realtime_t.unwrap_or(0.0) as i32,
+                    gametime: run.times.ingame_t.unwrap_or(0.0) as i32,
+                    comment: run.comment.unwrap_or(String::from("")),
+                    video: match run.videos
                     {
-                        "21gezkxl" => Region::NTSC,
-                        "jqzvzo4l" => Region::PAL,
-                        _ => Region::NTSC
-                    }
+                        Some(v) => if let Some(links) = v.links { if let Some(video) = links.first() { video.uri.clone().unwrap_or(String::from("")) } else { String::from("") }} else { String::from("") },
+                        _ => String::from("")
+                    },
+                    active: 1,
+                    date: run.date.unwrap_or(String::from("")),
+                    weblink: run.weblink.unwrap_or(String::from("")),
+                    credits: runners
+                };
+
+                records.push(record);
+            }
         }
-    }
 
-    fn get_category_urls(&self) -> Vec<&str>
-    {
-        let categories = vec![
-            "9d8v96lk?var-wle6dpr8=21gezkxl&embed=players",
-            "9d8v96lk?var-wle6dpr8=jqzvzo4l&embed=players",
-            "xd1mpewd?embed=players",
-            "ndx8qmvk?embed=players",
-            "w20zowod?embed=players",
-            "xd1mplwd?embed=players",
-            "n2y1y182?embed=players",
-            "w2018jok?embed=players",
-            "wdmqjw32?var-kn02d083=81w422oq&embed=players",
-            "wdmqjw32?var-kn02d083=5lmo33j1&embed=players",
-            "rklgyq8d?var-onv6jzw8=4lx07prl&embed=players",
-            "rklgyq8d?var-onv6jzw8=814k0yjl&embed=players",
-            "rklgyq8d?var-onv6jzw8=z195omkq&embed=players",
-            "rklgyq8d?var-onv6jzw8=p12oydkl&embed=players",
-            "rklgyq8d?var-onv6jzw8=xqk9yjy1&embed=players",
-            "rklgyq8d?var-onv6jzw8=gq79mjyl&embed=players",
-            "7kjrnrx2?var-wl360wwl=21gezknl&embed=players",
-            "7kjrnrx2?var-wl360wwl=jqzvzogl&embed=players",
-            "7kjrnrx2?var-wl360wwl=klrjo8jq&embed=players",
-        ];
-        categories
+        records
     }
 
-    fn map_category(&self, category_id: &String, values: &SrcValue) -> Category
+    fn resolve_player(player: &SrcPlayer, users: &Vec<SrcUser>) -> Option<records::RunnerRef>
     {
-        match category_id.as_str()
+        match player.rel.as_str()
         {
-            "9d8v96lk" => Category::AnyPercent,
-            "xd1mpewd" => Category::OneHundredPercent,
-            "ndx8qmvk" => Category::RBO,
-            "w20zowod" => Category::AnyPercentGlitched,
-            "xd1mplwd" => Category::MapCompletion,
-            "n2y1y182" => Category::CeresEscape,
-            "w2018jok" => Category::LowPercentGlitched,
-            "wdmqjw32" => 
-            {
-                match values.gt_code.as_ref().unwrap().as_str()
-                {
-                    "81w422oq" => Category::GTClassic,
-                    "5lmo33j1" => Category::AnyPercentGTCode,
-                    _ => Category::Unknown
-                }
-            },
-            "rklgyq8d" => 
+            "user" =>
             {
-                match values.low_percent.as_ref().unwrap().as_str()
-                {
-                    "4lx07prl" => Category::LowPercentIce,
-                    "814k0yjl" => Category::LowPercentSpeed,
-                    "z195omkq" => Category::LowPercentIceBooster,
-                    "p12oydkl" => Category::LowPercentXIce,
-                    "xqk9yjy1" => Category::LowPercentIceBoots,
-                    "gq79mjyl" => Category::LowPercentSpeedBoots,
-                    _ => Category::Unknown
-                }
+                let id = player.id.as_ref()?;
+                let user = users.iter().find(|u| u.id.as_ref() == Some(id))?;
+                let name = user.names.as_ref().map(|n| n.international.clone())?;
+                Some(records::RunnerRef::src_user(id, &name))
             },
-            "7kjrnrx2" =>
+            _ =>
             {
-                match values.miniboss.as_ref().unwrap().as_str()
-                {
-                    "21gezknl" => Category::SporeSpawnRTA,
-                    "jqzvzogl" => Category::CrocomireRTA,
-                    "klrjo8jq" => Category::BotwoonRTA,
-                    _ => Category::Unknown
-                }
+                /* Guests have no account, only the name on the run */
+                let name = player.name.as_ref()?;
+                Some(records::RunnerRef::guest(name))
             }
-            _ => Category::Unknown
         }
     }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use api::http::{HttpClient, FixtureTransport};
+    use models::records::{Category, Region};
+    use super::{Src, SrcBoardQuery, SrcLeaderboard, SrcPlayer};
+
+    /* The recorded Any% board for one value of the region variable */
+    fn recorded_board(region_value: &str) -> SrcLeaderboard
+    {
+        let mut variables = BTreeMap::new();
+        variables.insert(String::from("wle6dpr8"), String::from(region_value));
+        let query = SrcBoardQuery { category: String::from("9d8v96lk"), variables: variables, label: String::from("Any%") };
+
+        let http = HttpClient::with_transport("SRC", "https://www.speedrun.com/api/v1", Arc::new(FixtureTransport::new(PathBuf::from("fixtures"))));
+        let response = http.send(http.get(&query.path("nj1ne1p4"))).expect("The SRC leaderboard fixture is missing");
+        Src::parse_leaderboard(&response.body).unwrap()
+    }
+
+    #[test]
+    fn parses_recorded_leaderboard()
+    {
+        let board = recorded_board("21gezkxl");
+        assert_eq!(board.category, "9d8v96lk");
+        assert_eq!(board.runs.len(), 1);
+        assert_eq!(board.runs[0].place, 1);
+        assert_eq!(board.runs[0].run.id, "y8dwozoj");
+        assert_eq!(board.runs[0].run.status.status, "verified");
+        assert_eq!(board.runs[0].run.times.realtime_t, Some(2515.0));
+        assert_eq!(board.players.data[0].names.as_ref().map(|n| n.international.as_str()), Some("total"));
+    }
+
+    #[test]
+    fn rejects_responses_without_data()
+    {
+        assert!(Src::parse_leaderboard("{\"status\": 404, \"message\": \"Not found\"}").is_err());
+    }
+
+    #[test]
+    fn converts_leaderboards_to_records()
+    {
+        let records = Src::to_records(vec![recorded_board("21gezkxl"), recorded_board("jqzvzo4l")]);
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].id, 0);
+        assert_eq!(records[0].src_id, "y8dwozoj");
+        assert_eq!(records[0].category, Category::from_name("any%"));
+        assert_eq!(records[0].region, Region::NTSC);
+        assert_eq!(records[0].realtime, 2515);
+        assert_eq!(records[0].gametime, 0);
+        assert_eq!(records[0].video, "https://www.twitch.tv/videos/1");
+        assert_eq!(records[0].date, "2018-03-01");
+        assert_eq!(records[0].weblink, "https://www.speedrun.com/supermetroid/run/y8dwozoj");
+        assert_eq!(records[0].runner_id, 0);
+        assert_eq!(records[0].credits[0].src_id, "x7qz6qq8");
+        assert_eq!(records[0].runner_names(), "total");
+
+        assert_eq!(records[1].region, Region::PAL);
+        assert_eq!(records[1].realtime, 2730);
+        assert_eq!(records[1].runner_names(), "zoast");
+    }
+
+    #[test]
+    fn credits_every_player_including_guests()
+    {
+        let mut board = recorded_board("21gezkxl");
+        board.runs[0].run.players.push(SrcPlayer { rel: String::from("guest"), id: None, name: Some(String::from("guest runner")), uri: None });
+
+        let records = Src::to_records(vec![board]);
+        assert_eq!(records[0].credits.len(), 2);
+        assert_eq!(records[0].credits[1].src_id, "");
+        assert_eq!(records[0].runner_names(), "total & guest runner");
+    }
 
-    pub fn get_json(&self, run: &SrcRun) -> Result<String, Box<::std::error::Error>>
+    #[test]
+    fn skips_runs_without_a_category()
     {
-        let json = serde_json::to_string(run)?;
-        Ok(json)
+        let mut board = recorded_board("21gezkxl");
+        board.runs[0].run.category = Some(String::from("unmapped"));
+        assert_eq!(Src::to_records(vec![board]).len(), 0);
     }
 }
\ No newline at end of file
@@ -1,15 +1,18 @@
-use reqwest;
-use serde_json;
-use std::io::{Error, ErrorKind};
-
-header! { (ClientId, "Client-ID") => [String] }
-header! { (Authorization, "Authorization") => [String] }
+use std::env;
+use std::sync::Mutex;
+use chrono::Utc;
+use url::form_urlencoded;
+use api::http::{HttpClient, Request, Response, ApiError};
+use models::twitch_users::TwitchUserCache;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TwitchStream
 {
+    #[serde(default)]
     pub community_ids: Vec<String>,
     pub game_id: String,
+    #[serde(default)]
+    pub game_name: String,
     pub id: String,
     pub language: String,
     pub started_at: String,
@@ -18,6 +21,10 @@ pub struct TwitchStream
     #[serde(rename="type")]
     pub stream_type: String,
     pub user_id: String,
+    #[serde(default)]
+    pub user_login: String,
+    #[serde(default)]
+    pub user_name: String,
     pub viewer_count: i32
 }
 
@@ -33,170 +40,224 @@ pub struct TwitchUser
     pub description: String,
     pub profile_image_url: String,
     pub offline_image_url: String,
+    #[serde(default)]
     pub view_count: i32,
     pub email: Option<String>
 }
 
+/* One page of a Helix list, the cursor is missing on the last page */
+#[derive(Deserialize, Debug)]
+struct TwitchPage<T>
+{
+    data: Vec<T>,
+    #[serde(default)]
+    pagination: TwitchPagination
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TwitchPagination
+{
+    cursor: Option<String>
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct TwitchAuth
 {
     access_token: String,
-    refresh_token: String,
-    expires_in: i32,
-    scope: Vec<String>    
+    expires_in: i64
+}
+
+// The current app access token and when it stops being valid
+struct TwitchToken
+{
+    access_token: String,
+    expires_at: i64
 }
 
 pub struct Twitch
 {
     client_id: String,
     client_secret: String,
-    client_token: String
+    token: Mutex<TwitchToken>,
+    http: HttpClient
 }
 
 impl Twitch
 {
-    pub fn new(client_id: &String, client_secret: &String, client_token: &String) -> Option<Twitch>
+    pub fn new(client_id: &String, client_secret: &String) -> Option<Twitch>
     {
-        let auth = 
-            match client_token.as_str()
-            {
-                "" => Twitch::get_token(client_id, client_secret),
-                _ => Twitch::refresh_token(client_id, client_secret, client_token)
-            };
-
-        if let Ok(a) = auth
+        match Twitch::get_token(client_id, client_secret)
         {
-            Some(Twitch
+            Ok(token) => Some(Twitch
             {
-                client_secret: client_secret.clone(),
                 client_id: client_id.clone(),
-                client_token: a.access_token
-            })
-        } else 
-        {
-            None
+                client_secret: client_secret.clone(),
+                token: Mutex::new(token),
+                http: HttpClient::for_service("TWITCH", "https://api.twitch.tv/helix")
+            }),
+            Err(why) =>
+            {
+                error!("Could not get a twitch app access token: {}", why);
+                None
+            }
         }
+    }
 
-        /*
-        Some(Twitch
+    fn auth_client() -> HttpClient
+    {
+        HttpClient::for_service("TWITCH_AUTH", "https://id.twitch.tv/oauth2")
+    }
+
+    /* Client credentials flow, the token is valid for expires_in seconds */
+    fn get_token(client_id: &String, client_secret: &String) -> Result<TwitchToken, Box<::std::error::Error>>
+    {
+        /* The credentials go in the form body, request urls end up in the logs */
+        let form = form_urlencoded::Serializer::new(String::new())
+            .append_pair("client_id", client_id)
+            .append_pair("client_secret", client_secret)
+            .append_pair("grant_type", "client_credentials")
+            .finish();
+
+        let http = Twitch::auth_client();
+        let response = http.send(http.post("/token").header("Content-Type", "application/x-www-form-urlencoded").body(form))?;
+
+        let auth: TwitchAuth = response.json()?;
+        Ok(TwitchToken
         {
-            client_secret: client_secret,
-            client_id: client_id,
-            client_token: client_token
+            access_token: auth.access_token,
+            expires_at: Utc::now().timestamp() + auth.expires_in
         })
-        */
     }
-    
-    fn get_token(client_id: &String, client_secret: &String) -> Result<TwitchAuth, reqwest::Error>
+
+    /* Replaces the current token with a new one */
+    fn refresh(&self) -> Result<String, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let url = format!("https://api.twitch.tv/kraken/oauth2/token?client_id={}&client_secret={}&grant_type=client_credentials&scope=", client_id, client_secret);
-        let mut response = reqclient.post(url.as_str()).send()?;
-        let body = response.text()?;
+        let token = Twitch::get_token(&self.client_id, &self.client_secret)?;
+        let access_token = token.access_token.clone();
+        *self.token.lock().unwrap() = token;
+        info!("Refreshed the twitch app access token");
+        Ok(access_token)
+    }
 
-        let auth: TwitchAuth = serde_json::from_str(&body).unwrap();
-        Ok(auth)
+    /* The current token, refreshed TWITCH_TOKEN_MARGIN seconds before it expires */
+    fn access_token(&self) -> Result<String, Box<::std::error::Error>>
+    {
+        let margin: i64 = env::var("TWITCH_TOKEN_MARGIN").ok().and_then(|m| m.parse().ok()).unwrap_or(300);
+        let current = {
+            let token = self.token.lock().unwrap();
+            if Utc::now().timestamp() < token.expires_at - margin { Some(token.access_token.clone()) } else { None }
+        };
+
+        match current
+        {
+            Some(t) => Ok(t),
+            None => self.refresh()
+        }
     }
 
-    fn refresh_token(client_id: &String, client_secret: &String, client_token: &String) -> Result<TwitchAuth, reqwest::Error>
+    /* Sends a Helix request with the Client-ID and token, a 401 refreshes the token and retries once */
+    fn helix(&self, path: &str) -> Result<Response, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let url = format!("https://api.twitch.tv/kraken/oauth2/token?grant_type=refresh_token&refresh_token={}&client_id={}&client_secret={}", client_token, client_id, client_secret);
-        let mut response = reqclient.post(url.as_str()).send()?;
-        let body = response.text()?;
+        self.helix_request(self.http.get(path))
+    }
 
-        let auth: TwitchAuth = serde_json::from_str(&body).unwrap();
-        Ok(auth)
+    fn helix_request(&self, request: Request) -> Result<Response, Box<::std::error::Error>>
+    {
+        let token = self.access_token()?;
+        let response = self.http.send(self.authorize(request.clone(), &token))?;
+        if response.status != 401
+        {
+            return Ok(response);
+        }
+
+        warn!("Twitch rejected the access token, refreshing it");
+        let token = self.refresh()?;
+        self.http.send(self.authorize(request, &token))
     }
 
-    pub fn check_auth(&self) -> bool
+    fn authorize(&self, request: Request, token: &str) -> Request
     {
-        let reqclient = reqwest::Client::new();
-        match reqclient.get(format!("https://api.twitch.tv/helix/").as_str())
-            .header(Authorization(format!("Bearer {}", self.client_token.clone())))
-            .send()
-            {
-                Ok(_r) => true,
-                _ => 
-                {
-                    if let Ok(_refresh) = Twitch::refresh_token(&self.client_id, &self.client_secret, &self.client_token)
-                    {
-                        true
-                    }
-                    else
-                    {
-                        false
-                    }
-                }
-            }
+        request
+            .header("Client-ID", &self.client_id)
+            .header("Authorization", &format!("Bearer {}", token))
     }
 
+    /* Every live stream for the game, following the cursor through all pages */
     pub fn get_streams(&self, game_id: i32) -> Result<Vec<TwitchStream>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get(format!("https://api.twitch.tv/helix/streams?game_id={}", game_id).as_str())
-            .header(Authorization(format!("Bearer {}", self.client_token.clone())))
-            .send()?;
-
-        let body = response.text()?;
-        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-        
-        if let Some(streams) = data.pointer("/data")
+        let mut streams: Vec<TwitchStream> = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop
         {
-            if let Ok(twitch_streams) = serde_json::from_value::<Vec<TwitchStream>>(streams.clone())
+            let mut path = format!("/streams?game_id={}&first=100", game_id);
+            if let Some(ref c) = cursor
+            {
+                /* Cursors are opaque and can hold +, / and = */
+                let after: String = form_urlencoded::byte_serialize(c.as_bytes()).collect();
+                path.push_str(&format!("&after={}", after));
+            }
+
+            let page: TwitchPage<TwitchStream> = self.helix(&path)?.json()?;
+            let done = page.data.len() == 0;
+            streams.extend(page.data);
+            cursor = page.pagination.cursor;
+            if done || cursor.is_none()
             {
-                return Ok(twitch_streams);
+                break;
             }
         }
-        Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse twitch stream data")))
+        Ok(streams)
     }
 
-    pub fn get_user(&self, user_id: &String) -> Result<TwitchUser, Box<::std::error::Error>>
+    /* The streams of specific broadcasters that are live right now, asked for 100 at a time */
+    pub fn get_streams_by_user(&self, user_ids: &[String]) -> Result<Vec<TwitchStream>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get(format!("https://api.twitch.tv/helix/users?id={}", user_id).as_str())
-            .header(Authorization(format!("Bearer {}", self.client_token.clone())))
-            .send()?;
-
-        let body = response.text()?;
-        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-        
-        if let Some(streams) = data.pointer("/data")
+        let mut streams: Vec<TwitchStream> = Vec::new();
+        for chunk in user_ids.chunks(100)
         {
-            if let Ok(twitch_user) = serde_json::from_value::<Vec<TwitchUser>>(streams.clone())
-            {
-                if let Some(user) = twitch_user.first()
-                {
-                    return Ok(user.clone());
-                }
-            }
+            let query: Vec<String> = chunk.iter().map(|id| format!("user_id={}", id)).collect();
+            let fetched: Vec<TwitchStream> = self.helix(&format!("/streams?{}", query.join("&")))?.data("/data")?;
+            streams.extend(fetched);
         }
+        Ok(streams)
+    }
 
-        Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse twitch user data")))     
+    /* Asks twitch to deliver an EventSub event type for a broadcaster to the webhook callback */
+    pub fn subscribe(&self, event_type: &str, broadcaster_id: &str, callback: &str, secret: &str) -> Result<(), Box<::std::error::Error>>
+    {
+        let body = json!({
+            "type": event_type,
+            "version": "1",
+            "condition": { "broadcaster_user_id": broadcaster_id },
+            "transport": { "method": "webhook", "callback": callback, "secret": secret }
+        });
+
+        let request = self.http.post("/eventsub/subscriptions").header("Content-Type", "application/json").body(body.to_string());
+        let response = self.helix_request(request)?;
+        match response.status
+        {
+            /* 409 means the subscription already exists */
+            202 | 409 => Ok(()),
+            status => Err(Box::new(ApiError::Status(status, response.url)))
+        }
     }
 
-    pub fn get_user_by_name(&self, user_name: &String) -> Result<TwitchUser, Box<::std::error::Error>>
+    /* Users come from the local cache while they are younger than TWITCH_USER_CACHE_TTL seconds,
+       the rest are looked up 100 at a time and cached */
+    pub fn get_users_by_name(&self, user_names: &[String]) -> Result<Vec<TwitchUser>, Box<::std::error::Error>>
     {
-        let reqclient = reqwest::Client::new();
-        let mut response = reqclient.get(format!("https://api.twitch.tv/helix/users?login={}", user_name).as_str())
-            .header(Authorization(format!("Bearer {}", self.client_token.clone())))
-            .send()?;
-
-        let body = response.text()?;
-        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
-        
-        if let Some(streams) = data.pointer("/data")
+        let max_age: i64 = env::var("TWITCH_USER_CACHE_TTL").ok().and_then(|t| t.parse().ok()).unwrap_or(86400);
+        let mut users = TwitchUserCache::get_by_logins(user_names, max_age);
+
+        let missing: Vec<&String> = user_names.iter().filter(|n| !users.iter().any(|u| u.login == n.to_lowercase())).collect();
+        for chunk in missing.chunks(100)
         {
-            if let Ok(twitch_user) = serde_json::from_value::<Vec<TwitchUser>>(streams.clone())
-            {
-                if let Some(user) = twitch_user.first()
-                {
-                    return Ok(user.clone());
-                }
-            }
+            let query: Vec<String> = chunk.iter().map(|n| format!("login={}", n)).collect();
+            let fetched: Vec<TwitchUser> = self.helix(&format!("/users?{}", query.join("&")))?.data("/data")?;
+            TwitchUserCache::store(&fetched);
+            users.extend(fetched);
         }
 
-        Err(Box::new(Error::new(ErrorKind::InvalidData, "Could not parse twitch user data")))     
+        Ok(users)
     }
-}
-
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use chrono::Utc;
+use models::changeset::{Limits, Source};
+use services::records;
+use api::http::HttpClient;
+use api::eventsub::{self, EventSubHeaders};
+
+const IMPORT_USAGE: &str = "Usage: shaktool import <deertier|src> <file or directory> [--dry-run] [--force]";
+const REPLAY_USAGE: &str = "Usage: shaktool eventsub-replay <payload.json> [callback url]";
+
+/* Imports DeerTier or SRC dumps through the same merge pipeline as the live sync, returns the exit code */
+pub fn import(args: &[String]) -> i32
+{
+    let flags: Vec<&String> = args.iter().filter(|a| a.starts_with("--")).collect();
+    let params: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let dry_run = flags.iter().any(|f| f.as_str() == "--dry-run");
+    let force = flags.iter().any(|f| f.as_str() == "--force");
+
+    let (source, path) = match (params.get(0).and_then(|s| Source::from_name(s)), params.get(1))
+    {
+        (Some(s), Some(p)) => (s, Path::new(p.as_str())),
+        _ =>
+        {
+            eprintln!("{}", IMPORT_USAGE);
+            return 2;
+        }
+    };
+
+    let mut changes = match records::build_offline(source, path)
+    {
+        Ok(c) => c,
+        Err(why) =>
+        {
+            eprintln!("Could not read the {} dump: {}", source, why);
+            return 1;
+        }
+    };
+
+    println!("{}", changes.summary().replace("**", ""));
+    if dry_run
+    {
+        for line in changes.lines()
+        {
+            println!("{}", line.replace('`', ""));
+        }
+        return 0;
+    }
+
+    if let Some(reason) = changes.check(&Limits::from_env())
+    {
+        if !force
+        {
+            eprintln!("Not applied, {}. Check the changes with --dry-run and apply them with --force.", reason);
+            return 1;
+        }
+    }
+
+    changes.apply();
+    0
+}
+
+/* Signs a recorded EventSub payload with EVENTSUB_SECRET and delivers it to the receiver like twitch would, returns the exit code */
+pub fn eventsub_replay(args: &[String]) -> i32
+{
+    let path = match args.get(0)
+    {
+        Some(p) => Path::new(p.as_str()),
+        None =>
+        {
+            eprintln!("{}", REPLAY_USAGE);
+            return 2;
+        }
+    };
+
+    let mut body = String::new();
+    if let Err(why) = File::open(path).and_then(|mut f| f.read_to_string(&mut body))
+    {
+        eprintln!("Could not read {}: {}", path.display(), why);
+        return 1;
+    }
+
+    /* Each replay gets a new message id so the receiver does not drop it as a duplicate */
+    let now = Utc::now();
+    let mut headers = EventSubHeaders
+    {
+        message_id: format!("replay-{}-{}", now.timestamp(), now.timestamp_subsec_nanos()),
+        message_type: String::from(if body.contains("\"challenge\"") { "webhook_callback_verification" } else if body.contains("\"event\"") { "notification" } else { "revocation" }),
+        timestamp: now.to_rfc3339(),
+        signature: String::new()
+    };
+    headers.signature = eventsub::sign(&env::var("EVENTSUB_SECRET").unwrap_or(String::new()), &headers, &body);
+
+    let url = args.get(1).cloned().unwrap_or(format!("http://{}/", env::var("EVENTSUB_LISTEN").unwrap_or(String::from("127.0.0.1:8080")).replace("0.0.0.0", "127.0.0.1")));
+    let http = HttpClient::for_service("EVENTSUB_REPLAY", &url);
+    let request = http.post("")
+        .header("Content-Type", "application/json")
+        .header("Twitch-Eventsub-Message-Id", &headers.message_id)
+        .header("Twitch-Eventsub-Message-Type", &headers.message_type)
+        .header("Twitch-Eventsub-Message-Timestamp", &headers.timestamp)
+        .header("Twitch-Eventsub-Message-Signature", &headers.signature)
+        .body(body);
+
+    match http.send(request)
+    {
+        Ok(response) =>
+        {
+            println!("{} {}", response.status, response.body);
+            if response.is_success() { 0 } else { 1 }
+        },
+        Err(why) =>
+        {
+            eprintln!("Could not deliver the payload: {}", why);
+            1
+        }
+    }
+}
\ No newline at end of file
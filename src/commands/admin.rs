@@ -0,0 +1,121 @@
+use models::records::Record;
+use models::changeset::{Source, Limits};
+use services::records::{self, SyncOutcome};
+use paginator::Paginator;
+
+command!(rebuild(ctx, msg, args)
+{
+    let apply = match args.full().to_lowercase().as_str()
+    {
+        "" => true,
+        "check" => false,
+        _ =>
+        {
+            let _ = msg.channel_id.say("Usage: !rebuild [check]");
+            return Ok(());
+        }
+    };
+
+    let changes = match Record::rebuild_active(apply)
+    {
+        Ok(c) => c,
+        Err(e) =>
+        {
+            error!("Could not rebuild active records: {}", e);
+            let _ = msg.channel_id.say("Could not rebuild the active records, nothing was changed.");
+            return Ok(());
+        }
+    };
+
+    if changes.len() == 0
+    {
+        let _ = msg.channel_id.say("All active flags are consistent.");
+        return Ok(());
+    }
+
+    let lines: Vec<String> = changes.iter().map(|c|
+    {
+        format!("#{} {} {:?} {} :: {}", c.record_id, c.category, c.region, Record::from_seconds(c.realtime as i64), if c.active == 1 { "now active" } else { "no longer active" })
+    }).collect();
+
+    let header = if apply
+    {
+        format!("Rebuilt active flags, **{}** records changed", changes.len())
+    } else {
+        format!("Found **{}** records with inconsistent active flags", changes.len())
+    };
+
+    Paginator::new(&header, &lines, 15).send(ctx, msg.channel_id, msg.author.id);
+});
+
+command!(sync(ctx, msg, args)
+{
+    let source = match args.single::<String>().ok().and_then(|s| Source::from_name(&s))
+    {
+        Some(s) => s,
+        None =>
+        {
+            let _ = msg.channel_id.say("Usage: !sync <deertier|src> [dry|confirm]");
+            return Ok(());
+        }
+    };
+
+    let mode = args.single::<String>().unwrap_or(String::new()).to_lowercase();
+    match mode.as_str()
+    {
+        "dry" =>
+        {
+            match records::build(source)
+            {
+                Ok(changes) =>
+                {
+                    let mut header = format!("Dry run for {}", changes.summary());
+                    if let Some(reason) = changes.check(&Limits::from_env())
+                    {
+                        header.push_str(&format!("\nApplying would be held for confirmation: {}", reason));
+                    }
+                    Paginator::new(&header, &changes.lines(), 15).send(ctx, msg.channel_id, msg.author.id);
+                },
+                Err(why) =>
+                {
+                    error!("{} dry run failed: {:?}", source, why);
+                    let _ = msg.channel_id.say(&format!("Could not fetch the records from {}.", source));
+                }
+            }
+        },
+        "confirm" =>
+        {
+            match records::confirm(source)
+            {
+                Ok(Some(SyncOutcome::Applied(changes))) => { let _ = msg.channel_id.say(&format!("Applied {}", changes.summary())); },
+                Ok(Some(SyncOutcome::Held(changes, reason))) =>
+                {
+                    let _ = msg.channel_id.say(&format!("Not applied, {}.\n{}\nCheck the new changes with `!sync {} dry` and apply them with `!sync {} confirm`.", reason, changes.summary(), source.key(), source.key()));
+                },
+                Ok(None) => { let _ = msg.channel_id.say(&format!("There is no pending {} sync to confirm.", source)); },
+                Err(why) =>
+                {
+                    error!("{} sync failed: {:?}", source, why);
+                    let _ = msg.channel_id.say(&format!("Could not fetch the records from {}, the held sync is still pending.", source));
+                }
+            }
+        },
+        "" =>
+        {
+            match records::sync(source, false)
+            {
+                Ok(SyncOutcome::Applied(changes)) => { let _ = msg.channel_id.say(&format!("Applied {}", changes.summary())); },
+                Ok(SyncOutcome::Held(changes, reason)) =>
+                {
+                    let _ = msg.channel_id.say(&format!("Not applied, {}.\n{}\nCheck the changes with `!sync {} dry` and apply them with `!sync {} confirm`.", reason, changes.summary(), source.key(), source.key()));
+                },
+                Err(why) =>
+                {
+                    error!("{} sync failed: {:?}", source, why);
+                    let _ = msg.channel_id.say(&format!("Could not sync the records from {}.", source));
+                }
+            }
+        },
+        _ => { let _ = msg.channel_id.say("Usage: !sync <deertier|src> [dry|confirm]"); }
+    }
+});
\ No newline at end of file
@@ -1,30 +1,106 @@
 use std::env;
-use api::crocomire;
+use models::strats::Strat;
+use models::sync::SyncState;
+use models::records::Category;
+use paginator::Paginator;
 
-command!(version(ctx, msg) {
+command!(version(_ctx, msg) {
     let version = &env::var("CARGO_PKG_VERSION").unwrap_or(String::from("<unknown>"));
     let _ = msg.channel_id.say(format!("Shaktool™ by total - Version: {}", version));
 });
 
-command!(strat(ctx, msg, args) {
+command!(strat(_ctx, msg, args) {
     let strat = args.full();
     if strat.len() > 0
     {
-        if let Ok(strats) = crocomire::Strategy::find(&strat)
+        let age = match SyncState::age("strats")
+        {
+            Some(a) => a,
+            None =>
+            {
+                let _ = msg.channel_id.say("The strategy index has not been built yet, try again in a few minutes.");
+                return Ok(());
+            }
+        };
+
+        match Strat::search(&strat)
+        {
+            Ok(strats) =>
+            {
+                if strats.len() > 0
+                {
+                    let mut output = String::new();
+                    for s in strats
+                    {
+                        output.push_str(&format!("**{}** *({}/{})* :: http://crocomi.re/{}\n", s.name, s.area_name, s.room_name, s.id));            
+                    }
+                    output.push_str(&format!("*Strategy index updated {} ago.*", SyncState::age_str(age)));
+                    let _ = msg.channel_id.say(output);
+                } else {
+                    let _ = msg.channel_id.say(format!("No results found. *(Strategy index updated {} ago.)*", SyncState::age_str(age)));
+                }
+            },
+            Err(why) =>
+            {
+                error!("Strategy search failed: {:?}", why);
+                let _ = msg.channel_id.say("Could not search the strategy index.");
+            }
+        }
+    } else {
+        let _ = msg.channel_id.say("You need to specify a search string.");
+    }
+});
+
+command!(route(ctx, msg, args) {
+    let category_name = args.full();
+    if category_name.len() == 0
+    {
+        let _ = msg.channel_id.say("You need to specify a category.");
+        return Ok(());
+    }
+
+    /* Bot category aliases map onto crocomi.re's names, anything else is passed through as-is */
+    let category = Category::from_name(&category_name);
+    let crocomire_name = match category
+    {
+        Category::Unknown => category_name,
+        c => String::from(c.crocomire_name())
+    };
+
+    match Strat::get_by_category(&crocomire_name)
+    {
+        Ok(strats) =>
         {
             if strats.len() > 0
             {
-                let mut output = String::new();
+                let mut lines: Vec<String> = Vec::new();
+                let mut area = String::new();
+                let mut room = String::new();
                 for s in strats
                 {
-                    output.push_str(&format!("**{}** *({}/{})* :: http://crocomi.re/{}\n", s.name, s.area_name, s.room_name, s.id));            
+                    if s.area_name != area
+                    {
+                        area = s.area_name.clone();
+                        room = String::new();
+                        lines.push(format!("__**{}**__", area));
+                    }
+                    if s.room_name != room
+                    {
+                        room = s.room_name.clone();
+                        lines.push(format!("*{}*", room));
+                    }
+                    lines.push(format!("  {} **{}** :: <http://crocomi.re/{}>", s.difficulty_str(), s.name, s.id));
                 }
-                let _ = msg.channel_id.say(output);
+
+                Paginator::new(&format!("Route guide for: **{}**", crocomire_name), &lines, 25).send(ctx, msg.channel_id, msg.author.id);
             } else {
-                let _ = msg.channel_id.say("No results found.");
+                let _ = msg.channel_id.say("No strats found for the specified category.");
             }
+        },
+        Err(why) =>
+        {
+            error!("Route lookup failed: {:?}", why);
+            let _ = msg.channel_id.say("Could not search the strategy index.");
         }
-    } else {
-        let _ = msg.channel_id.say("You need to specify a search string.");
     }
 });
\ No newline at end of file
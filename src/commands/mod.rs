@@ -1,4 +1,21 @@
 pub mod streams;
 pub mod records;
 pub mod misc;
+pub mod stats;
+pub mod admin;
 
+use serenity;
+use serenity::model::Message;
+
+/* Checks whether the bot may post embeds in the channel the message came from */
+pub fn can_embed(msg: &Message) -> bool
+{
+    if let Some(guild) = msg.guild()
+    {
+        let user_id = serenity::CACHE.read().unwrap().user.id;
+        return guild.read().unwrap().permissions_in(msg.channel_id, user_id).embed_links();
+    }
+
+    /* Direct messages always allow embeds */
+    true
+}
\ No newline at end of file
@@ -1,23 +1,147 @@
 use models::records::{Category, Runner, Record};
+use models::provenance::{Provenance, Conflict};
+use paginator::Paginator;
+use serenity::model::Message;
+use commands::can_embed;
+use std::cmp::Ordering;
 
-command!(top(_ctx, msg, args)
+/* Replies with the details of a single run, as an embed when the channel allows it */
+fn send_record(msg: &Message, title: &str, record: &Record, runners: &str, rank: i32, details: &Vec<(String, String)>)
 {
-    let category_name = args.full();
+    if can_embed(msg)
+    {
+        let rank = Record::rank_str(rank);
+        let gametime = if record.gametime > 0 { record.gametime_str() } else { String::from("-") };
+        let date = if record.date != "" { record.date.clone() } else { String::from("-") };
+        let video = if record.video != "" { record.video.clone() } else { String::from("-") };
+        let source = record.source_link();
+
+        let sent = msg.channel_id.send_message(|m| m.embed(|e|
+        {
+            let e = e.title(title)
+                .description(&format!("**{}** by **{}**", record.realtime_str(), runners))
+                .field(|f| f.name("Category").value(&record.category.to_string()).inline(true))
+                .field(|f| f.name("Region").value(&record.region.to_string()).inline(true))
+                .field(|f| f.name("Timing").value(record.timing_str()).inline(true))
+                .field(|f| f.name("Rank").value(&rank).inline(true))
+                .field(|f| f.name("Real Time").value(&record.realtime_str()).inline(true))
+                .field(|f| f.name("Game Time").value(&gametime).inline(true))
+                .field(|f| f.name("Date").value(&date).inline(true))
+                .field(|f| f.name("Video").value(&video).inline(false));
+            let e = details.iter().fold(e, |e, &(ref name, ref value)| e.field(|f| f.name(name).value(value).inline(true)));
+            if source != "" { e.url(&source).field(|f| f.name("Source").value(&source).inline(false)) } else { e }
+        }));
+
+        if let Err(why) = sent
+        {
+            error!("Could not send record embed: {:?}", why);
+        }
+    } else {
+        let mut output = format!("{}:\n{} **{}** by **{}** ({}, {}) :: *{}* :: {}", title, Record::rank_str(rank), record.realtime_str(), runners, record.region, record.timing_str(), record.comment, record.video);
+        for &(ref name, ref value) in details
+        {
+            output.push_str(&format!("\n{}: {}", name, value));
+        }
+        let _ = msg.channel_id.say(output);
+    }
+}
+
+/* Context for a personal best: gaps to WR and the neighbouring ranks, percentile and improvement */
+fn pb_details(record: &Record, rank: i32) -> Vec<(String, String)>
+{
+    let mut details: Vec<(String, String)> = Vec::new();
+
+    if let Ok(wr) = Record::get_region_wr(record.category, record.region)
+    {
+        if wr.id != record.id
+        {
+            details.push((String::from("Gap to WR"), Record::delta_str(record.realtime - wr.realtime)));
+        }
+    }
+
+    if let Ok((above, below)) = record.get_neighbours()
+    {
+        if let Some(a) = above
+        {
+            details.push((String::from("Next rank up"), format!("{} ({})", Record::delta_str(record.realtime - a.realtime), a.runner_names())));
+        }
+        if let Some(b) = below
+        {
+            details.push((String::from("Next rank down"), format!("{} ({})", Record::delta_str(record.realtime - b.realtime), b.runner_names())));
+        }
+    }
+
+    if let Ok(total) = record.get_board_size()
+    {
+        if total > 0
+        {
+            details.push((String::from("Percentile"), format!("Top {:.1}% ({} of {})", rank as f64 * 100.0 / total as f64, rank, total)));
+        }
+    }
+
+    if let Ok(previous) = record.get_previous_pb()
+    {
+        details.push((String::from("Improvement"), format!("{} (from {})", Record::delta_str(record.realtime - previous.realtime), previous.realtime_str())));
+    }
+
+    let sources: Vec<String> = Provenance::for_record(record).iter().map(|p| p.describe()).collect();
+    if sources.len() > 0
+    {
+        details.push((String::from("Sources"), sources.join("\n")));
+    }
+
+    details
+}
+
+/* One line of a list reply; embeds can use masked links, plain messages can't */
+fn record_line(record: &Record, runners: &str, rank: i32, embed: bool) -> String
+{
+    if embed
+    {
+        let mut line = format!("{} **{}** by **{}**", Record::rank_str(rank), record.realtime_str(), runners);
+        if record.gametime > 0 { line.push_str(&format!(" *(IGT {})*", record.gametime_str())); }
+        if record.date != "" { line.push_str(&format!(" · {}", record.date)); }
+        if record.video != "" { line.push_str(&format!(" · [video]({})", record.video)); }
+        if record.source_link() != "" { line.push_str(&format!(" · [source]({})", record.source_link())); }
+        line
+    } else {
+        format!("{} **{}** by **{}** :: {}", Record::rank_str(rank), record.realtime_str(), runners, record.video)
+    }
+}
+
+/* Splits a trailing rank range like "11-30" off the arguments, defaulting to the top 10 */
+fn parse_range(input: &str) -> (String, i32, i32)
+{
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    if let Some(last) = words.last().cloned()
+    {
+        let bounds: Vec<i32> = last.split('-').filter_map(|b| b.parse().ok()).collect();
+        if bounds.len() == 2 && last.split('-').count() == 2 && bounds[0] >= 1 && bounds[1] >= bounds[0]
+        {
+            words.pop();
+            return (words.join(" "), bounds[0], bounds[1]);
+        }
+    }
+    (words.join(" "), 1, 10)
+}
+
+command!(top(ctx, msg, args)
+{
+    let (category_name, first, last) = parse_range(&args.full());
     let category = Category::from_name(&category_name);
-    if let Ok(records) = Record::get_top(category)
+    if let Ok(records) = Record::get_top(category, first - 1, last - first + 1)
     {
         if records.len() > 0
         {
-            let mut output = String::new();
-            let mut i = 1;
-            output.push_str(&format!("Top records for: **{}**\n", category));
+            let embed = can_embed(msg);
+            let mut lines: Vec<String> = Vec::new();
+            let mut i = first;
             for r in records
             {
-                let runner = Runner::from_id(r.runner_id).unwrap();
-                output.push_str(&format!("({}) **{}** by **{}** :: {}\n", i, r.realtime_str() , runner.name, r.video));
+                lines.push(record_line(&r, &r.runner_names(), i, embed));
                 i += 1;
             }
-            let _ = msg.channel_id.say(output);
+            Paginator::new(&format!("Top records for: **{}**", category), &lines, 10).embed(embed).send(ctx, msg.channel_id, msg.author.id);
             return Ok(());
         }
     }    
@@ -30,14 +154,13 @@ command!(wr(_ctx, msg, args)
     let category = Category::from_name(&category_name);
     if let Ok(record) = Record::get_wr(category)
     {
-        let runner = Runner::from_id(record.runner_id).unwrap();
-        let _ = msg.channel_id.say(format!("WR for *{}* is **{}** by **{}** :: *{}* :: {}", category, record.realtime_str(), runner.name, record.comment, record.video));
+        send_record(msg, &format!("World record for {}", category), &record, &record.runner_names(), 1, &Vec::new());
     } else {
         let _ = msg.channel_id.say("Could not find any records for the specified category.");
     }
 });
 
-command!(records(_ctx, msg, args)
+command!(records(ctx, msg, args)
 {
     let runner_name = args.full();
     if let Ok(runner) = Runner::from_name(&runner_name)
@@ -46,13 +169,13 @@ command!(records(_ctx, msg, args)
         {
             if records.len() > 0
             {
-                let mut output = String::new();
-                output.push_str(&format!("Current records for: **{}**\n", runner.name));
+                let embed = can_embed(msg);
+                let mut lines: Vec<String> = Vec::new();
                 for r in records
                 {
-                    output.push_str(&format!("**{}** ({}) **{}** :: {} :: {}\n", r.category, r.get_rank(), r.realtime_str(), r.comment, r.video));
+                    lines.push(format!("**{}** ({}) {}", r.category, r.region, record_line(&r, &r.runner_names(), r.get_rank().unwrap_or(0), embed)));
                 }
-                let _ = msg.channel_id.say(output);
+                Paginator::new(&format!("Current records for: **{}**", runner.name), &lines, 10).embed(embed).send(ctx, msg.channel_id, msg.author.id);
             } else {
                 let _ = msg.channel_id.say("No records found for this runner.");
             }
@@ -64,7 +187,7 @@ command!(records(_ctx, msg, args)
     }
 });
 
-command!(pb(ctx, msg, args) {
+command!(pb(_ctx, msg, args) {
     let runner_name = args.single::<String>();
     let category_name = args.full();
 
@@ -79,7 +202,15 @@ command!(pb(ctx, msg, args) {
                 let pb = Record::get_pb(r.id, category);
                 if let Ok(p) = pb
                 {                
-                    let _ = msg.channel_id.say(format!("Personal best for **{}** in *{}*:\n({}) **{}** :: {}", r.name, category, p.get_rank(), p.realtime_str(), p.video));
+                    match p.get_rank()
+                    {
+                        Ok(rank) => send_record(msg, &format!("Personal best for {} in {}", r.name, category), &p, &p.runner_names(), rank, &pb_details(&p, rank)),
+                        Err(why) =>
+                        {
+                            error!("Could not rank personal best: {:?}", why);
+                            let _ = msg.channel_id.say("Could not determine the rank of this personal best.");
+                        }
+                    }
                 } else {
                     let _ = msg.channel_id.say("No personal best found for this category.");
                 }                                
@@ -92,4 +223,159 @@ command!(pb(ctx, msg, args) {
     } else {
         let _ = msg.channel_id.say("You must specify a runner and a category.");
     }
+});
+
+command!(whatif(_ctx, msg, args) {
+    let input = args.full();
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let time = match words.pop()
+    {
+        Some(t) if words.len() > 0 => t,
+        _ =>
+        {
+            let _ = msg.channel_id.say("You must specify a category and a time.");
+            return Ok(());
+        }
+    };
+
+    let category = Category::from_name(&words.join(" "));
+    if category == Category::Unknown
+    {
+        let _ = msg.channel_id.say("The specified category does not exist.");
+        return Ok(());
+    }
+
+    let realtime = match Record::parse_time(time)
+    {
+        Ok(t) if t > 0 => t,
+        _ =>
+        {
+            let _ = msg.channel_id.say("Could not understand that time, try something like 42:30, 1:02:03.45 or 3723s.");
+            return Ok(());
+        }
+    };
+
+    match Record::rank_for_time(category, realtime)
+    {
+        Ok(rank) =>
+        {
+            let mut output = format!("A **{}** in *{}* would place {}", Record::from_seconds(realtime as i64), category, Record::rank_str(rank));
+            if let Ok(slower) = Record::get_slower(category, realtime, 6)
+            {
+                let mut names: Vec<String> = slower.iter().map(|r| format!("**{}**", r.runner_names())).collect();
+                if names.len() > 5
+                {
+                    names.truncate(5);
+                    names.push(String::from("more"));
+                }
+                if names.len() > 0
+                {
+                    output.push_str(&format!(", passing {}", names.join(", ")));
+                }
+            }
+            output.push('.');
+            let _ = msg.channel_id.say(output);
+        },
+        Err(why) =>
+        {
+            error!("Could not rank hypothetical time: {:?}", why);
+            let _ = msg.channel_id.say("Could not find any records for the specified category.");
+        }
+    }
+});
+
+command!(compare(ctx, msg, args) {
+    let (name_a, name_b) = match (args.single::<String>(), args.single::<String>())
+    {
+        (Ok(a), Ok(b)) => (a, b),
+        _ =>
+        {
+            let _ = msg.channel_id.say("You must specify two runners.");
+            return Ok(());
+        }
+    };
+
+    let (runner_a, runner_b) = match (Runner::from_name(&name_a), Runner::from_name(&name_b))
+    {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(_), _) => { let _ = msg.channel_id.say(format!("No records found for {}.", name_a)); return Ok(()); },
+        (_, Err(_)) => { let _ = msg.channel_id.say(format!("No records found for {}.", name_b)); return Ok(()); }
+    };
+
+    let records_a = Record::get_records(runner_a.id).unwrap_or(Vec::new());
+    let records_b = Record::get_records(runner_b.id).unwrap_or(Vec::new());
+
+    /* Boards are split by region, so a PAL and an NTSC run in the same category are not compared */
+    let mut lines: Vec<String> = Vec::new();
+    let mut only_a: Vec<String> = Vec::new();
+    let (mut wins_a, mut wins_b, mut ties) = (0, 0, 0);
+    for a in &records_a
+    {
+        match records_b.iter().find(|b| b.category == a.category && b.region == a.region)
+        {
+            Some(b) =>
+            {
+                let delta = a.realtime - b.realtime;
+                match delta.cmp(&0)
+                {
+                    Ordering::Less => wins_a += 1,
+                    Ordering::Greater => wins_b += 1,
+                    Ordering::Equal => ties += 1
+                }
+                lines.push(format!("**{}** ({}): {} {} vs {} {} :: {}",
+                    a.category, a.region,
+                    Record::rank_str(a.get_rank().unwrap_or(0)), a.realtime_str(),
+                    Record::rank_str(b.get_rank().unwrap_or(0)), b.realtime_str(),
+                    if delta == 0 { String::from("tied") } else { format!("{} ahead by {}", if delta < 0 { &runner_a.name } else { &runner_b.name }, Record::delta_str(delta.abs()).trim_start_matches('+')) }));
+            },
+            None => only_a.push(format!("{} ({})", a.category, a.region))
+        }
+    }
+    let only_b: Vec<String> = records_b.iter()
+        .filter(|b| !records_a.iter().any(|a| a.category == b.category && a.region == b.region))
+        .map(|b| format!("{} ({})", b.category, b.region))
+        .collect();
+
+    if lines.len() == 0 && only_a.len() == 0 && only_b.len() == 0
+    {
+        let _ = msg.channel_id.say("Neither runner has any records.");
+        return Ok(());
+    }
+
+    lines.push(format!("Tally: **{}** {} - {} **{}**{}", runner_a.name, wins_a, wins_b, runner_b.name, if ties > 0 { format!(" ({} tied)", ties) } else { String::new() }));
+    if only_a.len() > 0 { lines.push(format!("Only **{}** runs: {}", runner_a.name, only_a.join(", "))); }
+    if only_b.len() > 0 { lines.push(format!("Only **{}** runs: {}", runner_b.name, only_b.join(", "))); }
+
+    Paginator::new(&format!("**{}** vs **{}**", runner_a.name, runner_b.name), &lines, 15).send(ctx, msg.channel_id, msg.author.id);
+});
+
+command!(conflicts(ctx, msg, args)
+{
+    let category_name = args.full();
+    let category = if category_name == "" { None } else { Some(Category::from_name(&category_name)) };
+    if category == Some(Category::Unknown)
+    {
+        let _ = msg.channel_id.say("The specified category does not exist.");
+        return Ok(());
+    }
+
+    match Conflict::find(category)
+    {
+        Ok(ref c) if c.len() == 0 => { let _ = msg.channel_id.say("DeerTier and SRC agree on every run."); },
+        Ok(c) =>
+        {
+            let lines: Vec<String> = c.iter().map(|c| c.line()).collect();
+            let header = match category
+            {
+                Some(cat) => format!("Conflicts between DeerTier and SRC for: **{}**", cat),
+                None => String::from("Conflicts between DeerTier and SRC")
+            };
+            Paginator::new(&header, &lines, 10).send(ctx, msg.channel_id, msg.author.id);
+        },
+        Err(why) =>
+        {
+            error!("Could not look up conflicts: {:?}", why);
+            let _ = msg.channel_id.say("Could not look up the conflicts.");
+        }
+    }
 });
\ No newline at end of file
@@ -0,0 +1,119 @@
+use models::records::{Category, Region, Runner, Record};
+use models::stats::{RunnerStats, PointsFormula, OverallScore, CategoryStats};
+use paginator::Paginator;
+
+command!(stats(_ctx, msg, args)
+{
+    let runner_name = args.full();
+    let runner = match Runner::from_name(&runner_name)
+    {
+        Ok(r) => r,
+        Err(_) =>
+        {
+            let _ = msg.channel_id.say("No records found for this runner.");
+            return Ok(());
+        }
+    };
+
+    match RunnerStats::for_runner(runner.id)
+    {
+        Ok(ref s) if s.categories > 0 =>
+        {
+            let mut output = format!("Stats for: **{}**\n", runner.name);
+            output.push_str(&format!("Categories: **{}** :: Best rank: {} :: Average rank: **{:.1}**\n", s.categories, Record::rank_str(s.best_rank), s.average_rank));
+            output.push_str(&format!("Sum of PBs: **{}**\n", Record::from_seconds(s.total_time as i64)));
+            if s.first_run != ""
+            {
+                output.push_str(&format!("First run: {}\n", s.first_run));
+            }
+            if s.improvements.len() > 0
+            {
+                output.push_str("Recent improvements:\n");
+                for i in s.improvements.iter()
+                {
+                    output.push_str(&format!("  **{}** ({}) {} to **{}**{}\n", i.category, i.region, Record::delta_str(-i.gain), Record::from_seconds(i.realtime as i64), if i.date != "" { format!(" on {}", i.date) } else { String::new() }));
+                }
+            }
+            let _ = msg.channel_id.say(output);
+        },
+        Ok(_) =>
+        {
+            let _ = msg.channel_id.say("No records found for this runner.");
+        },
+        Err(why) =>
+        {
+            error!("Could not compute runner stats: {:?}", why);
+            let _ = msg.channel_id.say("Could not compute stats for this runner.");
+        }
+    }
+});
+
+command!(overall(ctx, msg)
+{
+    match OverallScore::ranking(&PointsFormula::from_env())
+    {
+        Ok(ranking) =>
+        {
+            if ranking.len() > 0
+            {
+                let mut lines: Vec<String> = Vec::new();
+                for (i, score) in ranking.iter().enumerate()
+                {
+                    let name = Runner::from_id(score.runner_id).map(|r| r.name).unwrap_or(String::from("?"));
+                    lines.push(format!("{} **{}** :: {:.1} points ({} boards)", Record::rank_str(i as i32 + 1), name, score.points, score.boards));
+                }
+                Paginator::new("Overall ranking across all categories", &lines, 15).send(ctx, msg.channel_id, msg.author.id);
+            } else {
+                let _ = msg.channel_id.say("Could not find any records.");
+            }
+        },
+        Err(why) =>
+        {
+            error!("Could not compute overall ranking: {:?}", why);
+            let _ = msg.channel_id.say("Could not compute the overall ranking.");
+        }
+    }
+});
+
+command!(catstats(_ctx, msg, args)
+{
+    let input = args.full();
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let region = match words.last().and_then(|w| Region::from_name(w))
+    {
+        Some(r) => { words.pop(); Some(r) },
+        None => None
+    };
+
+    let category = Category::from_name(&words.join(" "));
+    if category == Category::Unknown
+    {
+        let _ = msg.channel_id.say("The specified category does not exist.");
+        return Ok(());
+    }
+
+    match CategoryStats::for_board(category, region)
+    {
+        Ok(s) =>
+        {
+            let mut output = format!("Stats for: **{}**{}\n", category, region.map(|r| format!(" ({})", r)).unwrap_or(String::new()));
+            output.push_str(&format!("Active runners: **{}** :: New in 30 days: **{}** :: New in 90 days: **{}**\n", s.runners, s.new_30, s.new_90));
+            output.push_str(&format!("WR: **{}** :: Q1: **{}** :: Median: **{}** :: Q3: **{}**\n", Record::from_seconds(s.wr as i64), Record::from_seconds(s.quartiles.0 as i64), Record::from_seconds(s.quartiles.1 as i64), Record::from_seconds(s.quartiles.2 as i64)));
+            let barriers: Vec<String> = s.barriers.iter().map(|&(b, n)| format!("sub-{}: **{}**", Record::from_seconds(b as i64), n)).collect();
+            output.push_str(&format!("{}\n", barriers.join(" :: ")));
+
+            let largest = s.histogram.iter().map(|&(_, _, n)| n).max().unwrap_or(1).max(1);
+            output.push_str("```\n");
+            for &(from, to, n) in s.histogram.iter()
+            {
+                output.push_str(&format!("{:>8} - {:>8} | {:<20} {}\n", Record::from_seconds(from as i64), Record::from_seconds(to as i64), "█".repeat((n * 20 / largest) as usize), n));
+            }
+            output.push_str("```");
+            let _ = msg.channel_id.say(output);
+        },
+        Err(_) =>
+        {
+            let _ = msg.channel_id.say("Could not find any records for the specified category.");
+        }
+    }
+});
\ No newline at end of file
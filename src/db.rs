@@ -2,18 +2,46 @@ use rusqlite::Connection;
 use rusqlite;
 use std::collections::HashMap;
 
+/* Columns added to tables that existing databases already have, with the value existing rows get */
+const COLUMNS: &[(&str, &str, &str)] = &[
+    ("records", "date", ""),
+    ("records", "weblink", ""),
+    ("strats", "room_id", "0"),
+    ("records", "src_last_seen", ""),
+    ("records", "dt_last_seen", ""),
+    ("streams", "modified", "0"),
+    ("streams", "user_login", ""),
+    ("streams", "peak_viewers", "0"),
+    ("streams", "started_at", ""),
+    ("streams", "channel_id", ""),
+    ("streams", "message_id", ""),
+    ("streams", "updated", "0")
+];
+
+/* Tests never touch the bot's database, every connection they make gets an empty one */
+#[cfg(not(test))]
+const DATABASE: &str = "bot.db";
+#[cfg(test)]
+const DATABASE: &str = ":memory:";
+
 pub fn query(q: &str, args: &[&rusqlite::types::ToSql]) -> rusqlite::Result<Vec<HashMap<String, String>>>
 {
-    let conn = Connection::open("bot.db").unwrap();
-    let mut stmt = try!(conn.prepare(q));
+    let conn = Connection::open(DATABASE).unwrap();
+    query_with(&conn, q, args)
+}
+
+/* Same as query, but on an existing connection or transaction */
+pub fn query_with(conn: &Connection, q: &str, args: &[&rusqlite::types::ToSql]) -> rusqlite::Result<Vec<HashMap<String, String>>>
+{
+    let mut stmt = conn.prepare(q)?;
     let columns: Vec<String> = stmt.column_names().iter().map(|x| x.to_string()).collect();
-    let mut rows = try!(stmt.query(args));
+    let mut rows = stmt.query(args)?;
 
     let mut ret: Vec<HashMap<String, String>> = Vec::new();
 
     while let Some(result_row) = rows.next()
     {
-        let row = try!(result_row);
+        let row = result_row?;
         let mut r = HashMap::new();
         for i in 0..row.column_count() 
         {
@@ -25,7 +53,12 @@ pub fn query(q: &str, args: &[&rusqlite::types::ToSql]) -> rusqlite::Result<Vec<
                     let iv: i32 = row.get(i);
                     iv.to_string()
                 }
-                _ => row.get(i)
+                /* NULL, e.g. in a column that was added to an existing database, reads as empty */
+                _ =>
+                {
+                    let v: Option<String> = row.get(i);
+                    v.unwrap_or(String::new())
+                }
             };
             r.insert(column_name, column_value);
         }
@@ -38,9 +71,41 @@ pub fn query(q: &str, args: &[&rusqlite::types::ToSql]) -> rusqlite::Result<Vec<
 
 pub fn exec(q: &str, args: &[&rusqlite::types::ToSql]) -> rusqlite::Result<i32>
 {
-    let conn = Connection::open("bot.db").unwrap();
-    let mut stmt = try!(conn.prepare(q));
-    let rows = try!(stmt.execute(args));
+    let conn = Connection::open(DATABASE).unwrap();
+    let mut stmt = conn.prepare(q)?;
+    let rows = stmt.execute(args)?;
+
+    Ok(rows as i32)
+}
+
+pub fn transaction<F>(f: F) -> rusqlite::Result<()> where F: FnOnce(&rusqlite::Transaction) -> rusqlite::Result<()>
+{
+    let mut conn = Connection::open(DATABASE).unwrap();
+    let tx = conn.transaction()?;
+    f(&tx)?;
+    tx.commit()
+}
+
+/* Creates any missing tables from the schema and adds the columns older databases lack, safe to run on every start */
+pub fn migrate() -> rusqlite::Result<()>
+{
+    let conn = Connection::open(DATABASE)?;
+    conn.execute_batch(include_str!("../bot.db.sql"))?;
+
+    for &(table, column, default) in COLUMNS
+    {
+        let existing: Vec<String> = {
+            let mut stmt = conn.prepare(&format!("PRAGMA table_info(`{}`)", table))?;
+            let names = stmt.query_map(&[], |row| row.get::<_, String>(1))?;
+            names.collect::<rusqlite::Result<Vec<String>>>()?
+        };
+
+        if !existing.iter().any(|c| c == column)
+        {
+            info!("Adding column {}.{} to the database", table, column);
+            conn.execute(&format!("ALTER TABLE `{}` ADD COLUMN `{}` TEXT DEFAULT '{}'", table, column, default), &[])?;
+        }
+    }
 
-    Ok(rows)    
+    Ok(())
 }
\ No newline at end of file
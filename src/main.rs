@@ -1,31 +1,47 @@
+// Written against Rust 2015, where trait objects are spelled without `dyn`
+#![allow(bare_trait_objects)]
+// House style: `field: field` initialisers, `== ""`/`len() == 0` checks, `unwrap_or(String::new())`, `push_str(&format!(..))` and `&String` parameters
+#![allow(clippy::redundant_field_names, clippy::comparison_to_empty, clippy::len_zero, clippy::or_fun_call, clippy::format_push_string, clippy::ptr_arg)]
+// NTSC and PAL are the regions' proper names
+#![allow(clippy::upper_case_acronyms)]
+
 #[macro_use] extern crate log;
 #[macro_use] extern crate serenity;
 #[macro_use] extern crate serde_derive;
-#[macro_use] extern crate hyper;
 #[macro_use] extern crate num_derive;
+#[macro_use] extern crate lazy_static;
 extern crate env_logger;
 extern crate kankyo;
 extern crate serde;
-extern crate serde_json;
+#[macro_use] extern crate serde_json;
 extern crate reqwest;
+extern crate url;
 extern crate chrono;
 extern crate rusqlite;
 extern crate time;
 extern crate num;
+extern crate typemap;
+extern crate hyper;
+extern crate futures;
+extern crate hmac;
+extern crate sha2;
+extern crate hex;
 
 mod db;
 mod models;
 mod api;
 mod commands;
 mod services;
+mod paginator;
+mod cli;
 
 use serenity::client::Client;
 use serenity::prelude::*;
 use serenity::framework::standard::{StandardFramework, help_commands};
 use serenity::model::event::ResumedEvent;
 use serenity::model::Ready;
-use serenity::model::ChannelId;
-use serenity::http;
+use serenity::model::Reaction;
+use serenity::model::UserId;
 use std::collections::HashSet;
 use std::env;
 use std::thread;
@@ -42,6 +58,11 @@ impl EventHandler for Handler
     {
         info!("Resumed");
     }
+
+    fn on_reaction_add(&self, ctx: Context, reaction: Reaction)
+    {
+        paginator::Paginator::on_reaction(&ctx, &reaction);
+    }
 }
 
 
@@ -50,10 +71,36 @@ fn main()
     kankyo::load().expect("Failed to load .env-file.");
     env_logger::init();
 
+    /* Bring the database up to the current schema before anything reads from it */
+    if let Err(why) = db::migrate()
+    {
+        error!("Could not migrate the database: {}", why);
+        ::std::process::exit(1);
+    }
+
+    /* Load the category definitions before anything touches the records */
+    models::categories::Category::init();
+
+    /* Offline import mode: shaktool import <deertier|src> <path> */
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "import"
+    {
+        ::std::process::exit(cli::import(&args[2..]));
+    }
+
+    /* Delivers a recorded EventSub payload to the receiver: shaktool eventsub-replay <payload.json> [url] */
+    if args.len() > 1 && args[1] == "eventsub-replay"
+    {
+        ::std::process::exit(cli::eventsub_replay(&args[2..]));
+    }
+
     let mut client = Client::new(&env::var("DISCORD_TOKEN").unwrap(), Handler);
 
+    /* Discord user ids allowed to run the admin commands */
+    let owners: HashSet<UserId> = env::var("BOT_OWNERS").unwrap_or(String::new()).split(',').filter_map(|id| id.trim().parse::<u64>().ok()).map(UserId).collect();
+
     client.with_framework(StandardFramework::new()
-        .configure(|c| c.prefix("!"))
+        .configure(|c| c.prefix("!").owners(owners))
         .command("help", |c| c.exec_help(help_commands::plain))
         .group("Streams", |g| g
             .command("addstream", |c| c
@@ -78,8 +125,8 @@ fn main()
         )
         .group("Records", |g| g
             .command("top", |c| c
-                .desc("Displays the top 10 records for the specified category.")
-                .example("100%")
+                .desc("Displays the top records for the specified category, optionally for a range of ranks.")
+                .example("any% 11-30")
                 .min_args(1)
                 .exec(commands::records::top))
             .command("records", |c| c
@@ -97,22 +144,76 @@ fn main()
                 .example("any%")
                 .min_args(1)
                 .exec(commands::records::wr))
+            .command("whatif", |c| c
+                .desc("Displays the rank a hypothetical time would get in the specified category.")
+                .example("any% 42:30")
+                .min_args(2)
+                .exec(commands::records::whatif))
+            .command("compare", |c| c
+                .desc("Compares the records of two runners in every category they both run.")
+                .example("total zoast")
+                .num_args(2)
+                .exec(commands::records::compare))
+            .command("conflicts", |c| c
+                .desc("Lists runs where DeerTier and SRC disagree, optionally for one category.")
+                .example("any%")
+                .exec(commands::records::conflicts))
+        )
+        .group("Stats", |g| g
+            .command("stats", |c| c
+                .desc("Displays a summary of the records for the specified person.")
+                .example("total")
+                .num_args(1)
+                .exec(commands::stats::stats))
+            .command("overall", |c| c
+                .desc("Displays the overall ranking of runners across all categories.")
+                .example("")
+                .exec(commands::stats::overall))
+            .command("catstats", |c| c
+                .desc("Displays the time distribution of the specified category, optionally for one region.")
+                .example("any% pal")
+                .min_args(1)
+                .exec(commands::stats::catstats))
         )
         .group("Misc", |g| g
             .command("strat", |c| c
-                .desc("Searches crocomi.re for one or more strategies.")
+                .desc("Searches the local crocomi.re strategy index for one or more strategies.")
                 .example("mockball")
                 .min_args(1)
                 .exec(commands::misc::strat))
+            .command("route", |c| c
+                .desc("Lists the crocomi.re strats for a category grouped by area and room.")
+                .example("14% ice")
+                .min_args(1)
+                .exec(commands::misc::route))
             .command("version", |c| c
                 .desc("Displays the version of the bot.")
                 .example("")
                 .exec(commands::misc::version))
         )
+        .group("Admin", |g| g
+            .command("rebuild", |c| c
+                .desc("Recomputes the active personal bests for every runner, or only reports the differences with 'check'.")
+                .example("check")
+                .owners_only(true)
+                .exec(commands::admin::rebuild))
+            .command("sync", |c| c
+                .desc("Syncs the records from DeerTier or SRC. 'dry' only shows the changes, 'confirm' applies a sync that was held back by the safety limits, if recomputing it gives the same changes.")
+                .example("src dry")
+                .min_args(1)
+                .owners_only(true)
+                .exec(commands::admin::sync))
+        )
     );
 
-    /* Start thread that polls twitch for stream notifications and reports to the correct channel */
-    let stream_service = thread::spawn(move || services::streams::worker());
+    /* Start thread that polls twitch (or receives EventSub messages) for stream notifications and reports to the correct channel */
+    thread::spawn(services::streams::worker);
+
+    /* Start thread that keeps the local crocomi.re strategy index up to date */
+    thread::spawn(services::strats::worker);
+
+    /* Start thread that syncs records from DeerTier and speedrun.com */
+    thread::spawn(services::records::worker);
 
     if let Err(why) = client.start()
     {
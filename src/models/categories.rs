@@ -0,0 +1,196 @@
+use models::records::Region;
+use serde_json;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use db;
+
+// Categories are described in a definition file instead of being hard-coded, see categories.json
+#[derive(Deserialize, Debug)]
+pub struct CategoryDefinition
+{
+    pub key: String,
+    pub name: String,
+    #[serde(default)]
+    pub legacy_id: Option<i32>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub crocomire: Option<String>,
+    #[serde(default)]
+    pub region: Region,
+    #[serde(default)]
+    pub timing: Timing,
+    #[serde(default)]
+    pub deertier: Vec<DeerTierMapping>,
+    #[serde(default)]
+    pub src: Vec<SrcMapping>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeerTierMapping
+{
+    pub key: String,
+    pub region: Option<Region>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SrcMapping
+{
+    pub category: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    pub region: Option<Region>
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Timing
+{
+    #[serde(rename="realtime")]
+    RealTime,
+    #[serde(rename="gametime")]
+    GameTime
+}
+
+impl Default for Timing
+{
+    fn default() -> Timing
+    {
+        Timing::RealTime
+    }
+}
+
+impl fmt::Display for Timing
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Timing::RealTime => write!(f, "Real Time"),
+            Timing::GameTime => write!(f, "Game Time")
+        }
+    }
+}
+
+lazy_static!
+{
+    static ref DEFINITIONS: Vec<CategoryDefinition> = CategoryDefinition::load().expect("Failed to load category definitions.");
+}
+
+impl CategoryDefinition
+{
+    fn load() -> Result<Vec<CategoryDefinition>, Box<::std::error::Error>>
+    {
+        let path = env::var("CATEGORIES_FILE").unwrap_or(String::from("categories.json"));
+        let mut contents = String::new();
+        File::open(&path)?.read_to_string(&mut contents)?;
+        let definitions: Vec<CategoryDefinition> = serde_json::from_str(&contents)?;
+        Ok(definitions)
+    }
+}
+
+// A handle to one of the loaded category definitions
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Category(usize);
+
+#[allow(non_upper_case_globals)]
+impl Category
+{
+    pub const Unknown: Category = Category(::std::usize::MAX);
+
+    pub fn definition(&self) -> Option<&'static CategoryDefinition>
+    {
+        DEFINITIONS.get(self.0)
+    }
+
+    /* Every defined category, in definition file order */
+    pub fn all() -> Vec<Category>
+    {
+        (0..DEFINITIONS.len()).map(Category).collect()
+    }
+
+    fn find<F>(f: F) -> Category where F: Fn(&CategoryDefinition) -> bool
+    {
+        DEFINITIONS.iter().position(f).map(Category).unwrap_or(Category::Unknown)
+    }
+
+    pub fn from_name(category: &str) -> Category
+    {
+        let name = category.trim().to_lowercase();
+        Category::find(|d| d.name.to_lowercase() == name || d.key.to_lowercase() == name || d.aliases.iter().any(|a| a.to_lowercase() == name))
+    }
+
+    pub fn from_dt_category(category: &str) -> Category
+    {
+        Category::find(|d| d.deertier.iter().any(|m| m.key == category))
+    }
+
+    /* Finds the category and region for an SRC category id and the variable values of a run */
+    pub fn from_src(category_id: &str, values: &HashMap<String, String>) -> (Category, Region)
+    {
+        for (i, d) in DEFINITIONS.iter().enumerate()
+        {
+            for m in d.src.iter()
+            {
+                if m.category == category_id && m.variables.iter().all(|(k, v)| values.get(k) == Some(v))
+                {
+                    return (Category(i), m.region.unwrap_or(d.region));
+                }
+            }
+        }
+        (Category::Unknown, Region::NTSC)
+    }
+
+    pub fn from_db(key: &str) -> Category
+    {
+        Category::find(|d| d.key == key)
+    }
+
+    pub fn to_db(self) -> String
+    {
+        match self.definition()
+        {
+            Some(d) => d.key.clone(),
+            None => String::from("Unknown")
+        }
+    }
+
+    pub fn crocomire_name(&self) -> &'static str
+    {
+        self.definition().and_then(|d| d.crocomire.as_ref()).map(|c| c.as_str()).unwrap_or("")
+    }
+
+    pub fn timing(&self) -> Timing
+    {
+        self.definition().map(|d| d.timing).unwrap_or(Timing::RealTime)
+    }
+
+    /* Loads the definitions and rewrites records stored with the old enum discriminants to their stable keys */
+    pub fn init()
+    {
+        info!("Loaded {} category definitions", DEFINITIONS.len());
+        for d in DEFINITIONS.iter()
+        {
+            if let Some(legacy_id) = d.legacy_id
+            {
+                let _ = db::exec("UPDATE records SET category=? WHERE category=?", &[&d.key, &legacy_id.to_string()]);
+            }
+        }
+        let _ = db::exec("UPDATE records SET category='Unknown' WHERE category='19'", &[]);
+    }
+}
+
+impl fmt::Display for Category
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.definition()
+        {
+            Some(d) => write!(f, "{}", d.name),
+            None => write!(f, "Unknown")
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,299 @@
+use models::records::{Record, MatchKind};
+use models::sync::SyncState;
+use models::provenance::Provenance;
+use chrono::Utc;
+use std::env;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Source
+{
+    DeerTier,
+    Src
+}
+
+impl Source
+{
+    pub fn from_name(name: &str) -> Option<Source>
+    {
+        match name.to_lowercase().as_str()
+        {
+            "dt" | "deertier" => Some(Source::DeerTier),
+            "src" | "speedrun.com" => Some(Source::Src),
+            _ => None
+        }
+    }
+
+    /* Key used for the sync state of the source */
+    pub fn key(&self) -> &'static str
+    {
+        match *self
+        {
+            Source::DeerTier => "deertier",
+            Source::Src => "src"
+        }
+    }
+}
+
+impl fmt::Display for Source
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Source::DeerTier => write!(f, "DeerTier"),
+            Source::Src => write!(f, "SRC")
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Change
+{
+    /* A run that is not stored yet */
+    New(Record),
+    /* A stored run that gains data from the source */
+    Updated(Record),
+    /* A stored run matched by video or comment, the incoming runners (by name) are merged into the stored one */
+    Merged(Record, String),
+    /* A PB that stops being active */
+    Deactivated(Record),
+    /* A run that no source lists anymore */
+    Removed(Record)
+}
+
+// Thresholds above which a change set is not applied without confirmation
+pub struct Limits
+{
+    pub max_deactivated_pct: f64,
+    pub max_new_pct: f64
+}
+
+impl Limits
+{
+    pub fn from_env() -> Limits
+    {
+        Limits
+        {
+            max_deactivated_pct: env::var("SYNC_MAX_DEACTIVATED_PCT").ok().and_then(|v| v.parse().ok()).unwrap_or(10.0),
+            max_new_pct: env::var("SYNC_MAX_NEW_PCT").ok().and_then(|v| v.parse().ok()).unwrap_or(25.0)
+        }
+    }
+}
+
+// Everything a sync would do to the records, computed before anything is written
+#[derive(Clone)]
+pub struct ChangeSet
+{
+    pub source: Source,
+    pub incoming: Vec<Record>,
+    pub removals: Vec<Record>,
+    pub changes: Vec<Change>,
+    pub active_total: i32,
+    /* Source ids listed in payloads that did not change since they were last applied, these skip the merge */
+    pub unchanged: Vec<String>,
+    /* Payload hashes to remember once the change set is applied */
+    pub payloads: Vec<(String, String)>,
+    /* Parts of the source that could not be loaded, or that no category maps onto, and are left out of the change set */
+    pub warnings: Vec<String>,
+    /* Unix timestamp of when the change set was computed */
+    pub created: i64
+}
+
+impl ChangeSet
+{
+    /* Incoming are the runs the source lists, removals the stored runs the source no longer lists */
+    pub fn build(source: Source, incoming: Vec<Record>, removals: Vec<Record>) -> ChangeSet
+    {
+        let mut changes: Vec<Change> = Vec::new();
+        let mut deactivated: Vec<i32> = Vec::new();
+
+        for record in incoming.iter().filter(|r| r.id == 0)
+        {
+            match record.find_existing()
+            {
+                Some((existing, kind)) =>
+                {
+                    if kind != MatchKind::Runner && existing.runner_id != record.runner_id
+                    {
+                        changes.push(Change::Merged(existing, record.runner_names()));
+                    } else if ChangeSet::fills(record, &existing) {
+                        changes.push(Change::Updated(existing));
+                    }
+                },
+                None =>
+                {
+                    changes.push(Change::New(record.clone()));
+                    for runner_id in record.runners.iter()
+                    {
+                        if let Some(pb) = Record::get_active(*runner_id, record.category, record.region)
+                        {
+                            if record.pb_time() != 0 && record.pb_time() < pb.pb_time() && !deactivated.contains(&pb.id)
+                            {
+                                deactivated.push(pb.id);
+                                changes.push(Change::Deactivated(pb));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for record in removals.iter()
+        {
+            let remaining = match source
+            {
+                Source::DeerTier => record.src_id != "",
+                Source::Src => record.dt_id != 0
+            };
+
+            if remaining
+            {
+                changes.push(Change::Updated(record.clone()));
+            } else {
+                changes.push(Change::Removed(record.clone()));
+                if record.active == 1 && !deactivated.contains(&record.id)
+                {
+                    deactivated.push(record.id);
+                    changes.push(Change::Deactivated(record.clone()));
+                }
+            }
+        }
+
+        ChangeSet
+        {
+            source: source,
+            incoming: incoming,
+            removals: removals,
+            changes: changes,
+            active_total: Record::count_active(),
+            unchanged: Vec::new(),
+            payloads: Vec::new(),
+            warnings: Vec::new(),
+            created: Utc::now().timestamp()
+        }
+    }
+
+    /* Whether saving the incoming run would fill in anything the stored run is missing */
+    fn fills(incoming: &Record, existing: &Record) -> bool
+    {
+        (existing.dt_id == 0 && incoming.dt_id != 0) || (existing.src_id == "" && incoming.src_id != "") || (existing.date == "" && incoming.date != "") || (existing.weblink == "" && incoming.weblink != "")
+    }
+
+    pub fn count_new(&self) -> usize
+    {
+        self.changes.iter().filter(|c| matches!(**c, Change::New(_))).count()
+    }
+
+    pub fn count_updated(&self) -> usize
+    {
+        self.changes.iter().filter(|c| matches!(**c, Change::Updated(_))).count()
+    }
+
+    pub fn count_merged(&self) -> usize
+    {
+        self.changes.iter().filter(|c| matches!(**c, Change::Merged(_, _))).count()
+    }
+
+    pub fn count_deactivated(&self) -> usize
+    {
+        self.changes.iter().filter(|c| matches!(**c, Change::Deactivated(_))).count()
+    }
+
+    pub fn count_removed(&self) -> usize
+    {
+        self.changes.iter().filter(|c| matches!(**c, Change::Removed(_))).count()
+    }
+
+    /* Returns the reason the change set should not be applied without confirmation, if any.
+       An empty board has nothing to protect, so the first sync is always allowed. */
+    pub fn check(&self, limits: &Limits) -> Option<String>
+    {
+        if self.active_total == 0
+        {
+            return None;
+        }
+
+        let deactivated_pct = self.count_deactivated() as f64 * 100.0 / self.active_total as f64;
+        if deactivated_pct > limits.max_deactivated_pct
+        {
+            return Some(format!("{:.1}% of the active records would be deactivated (limit {}%)", deactivated_pct, limits.max_deactivated_pct));
+        }
+
+        let new_pct = self.count_new() as f64 * 100.0 / self.active_total as f64;
+        if new_pct > limits.max_new_pct
+        {
+            return Some(format!("{:.1}% new records would be added (limit {}%)", new_pct, limits.max_new_pct));
+        }
+
+        None
+    }
+
+    pub fn summary(&self) -> String
+    {
+        let mut summary = format!("{}: **{}** new, **{}** updated, **{}** merged, **{}** deactivated, **{}** removed, **{}** unchanged", self.source, self.count_new(), self.count_updated(), self.count_merged(), self.count_deactivated(), self.count_removed(), self.unchanged.len());
+        if self.warnings.len() > 0
+        {
+            summary.push_str(&format!(", **{}** not loaded", self.warnings.len()));
+        }
+        summary
+    }
+
+    /* One line per change, in a diff-like format */
+    pub fn lines(&self) -> Vec<String>
+    {
+        let warnings = self.warnings.iter().map(|w| format!("`?` {}", w));
+        warnings.chain(self.changes.iter().map(|c|
+        {
+            match *c
+            {
+                Change::New(ref r) => format!("`+` {} {} {} :: {}", r.category, r.region, r.realtime_str(), r.runner_names()),
+                Change::Updated(ref r) => format!("`~` #{} {} {} {} :: {}", r.id, r.category, r.region, r.realtime_str(), r.runner_names()),
+                Change::Merged(ref r, ref from) => format!("`=` #{} {} {} {} :: {} merged into {}", r.id, r.category, r.region, r.realtime_str(), from, r.runner_names()),
+                Change::Deactivated(ref r) => format!("`!` #{} {} {} {} :: {} no longer PB", r.id, r.category, r.region, r.realtime_str(), r.runner_names()),
+                Change::Removed(ref r) => format!("`-` #{} {} {} {} :: {}", r.id, r.category, r.region, r.realtime_str(), r.runner_names())
+            }
+        })).collect()
+    }
+
+    pub fn apply(&mut self)
+    {
+        let source = self.source;
+        let started = Utc::now().timestamp();
+
+        for record in self.incoming.iter_mut()
+        {
+            let kind = record.save();
+            Provenance::record(record, source, kind, started);
+            match source
+            {
+                Source::DeerTier => Record::mark_seen_dt(record.dt_id, started),
+                Source::Src => Record::mark_seen_src(&record.src_id, started)
+            }
+        }
+
+        for record in self.removals.iter_mut()
+        {
+            record.drop_source(source == Source::Src, source == Source::DeerTier);
+            Provenance::remove(record.id, source);
+        }
+
+        for source_id in self.unchanged.iter()
+        {
+            Provenance::touch(source, source_id, started);
+            match source
+            {
+                Source::DeerTier => Record::mark_seen_dt(source_id.parse().unwrap_or(0), started),
+                Source::Src => Record::mark_seen_src(source_id, started)
+            }
+        }
+
+        for &(ref key, ref hash) in self.payloads.iter()
+        {
+            SyncState::store_payload(key, hash);
+        }
+
+        SyncState::touch(source.key());
+        info!("Applied {}", self.summary());
+    }
+}
\ No newline at end of file
@@ -1,2 +1,9 @@
 pub mod records;
-pub mod streams;
\ No newline at end of file
+pub mod categories;
+pub mod streams;
+pub mod strats;
+pub mod sync;
+pub mod stats;
+pub mod changeset;
+pub mod provenance;
+pub mod twitch_users;
\ No newline at end of file
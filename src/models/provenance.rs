@@ -0,0 +1,151 @@
+use db;
+use models::records::{Record, Runner, Category, MatchKind};
+use models::changeset::Source;
+use models::sync::SyncState;
+use chrono::Utc;
+
+// Where a stored run came from and how it was linked to that source
+pub struct Provenance
+{
+    pub source: Source,
+    pub source_id: String,
+    pub matched_by: String,
+    pub last_seen: Option<i64>
+}
+
+impl Provenance
+{
+    /* Remembers that a source listed the run, the way it was first matched is kept */
+    pub fn record(record: &Record, source: Source, kind: MatchKind, timestamp: i64)
+    {
+        let source_id = match source
+        {
+            Source::DeerTier => record.dt_id.to_string(),
+            Source::Src => record.src_id.clone()
+        };
+
+        let _ = db::exec("INSERT OR IGNORE INTO record_sources (record_id, source, source_id, matched_by, first_seen, last_seen) VALUES (?, ?, ?, ?, ?, ?)", &[&record.id.to_string(), &source.key(), &source_id, &kind.to_db(), &timestamp.to_string(), &timestamp.to_string()]);
+        let _ = db::exec("UPDATE record_sources SET source_id=?, last_seen=? WHERE record_id=? AND source=?", &[&source_id, &timestamp.to_string(), &record.id.to_string(), &source.key()]);
+    }
+
+    /* The source still lists the run, without anything about it having changed */
+    pub fn touch(source: Source, source_id: &str, timestamp: i64)
+    {
+        let _ = db::exec("UPDATE record_sources SET last_seen=? WHERE source=? AND source_id=?", &[&timestamp.to_string(), &source.key(), &source_id]);
+    }
+
+    /* The source stopped listing the run */
+    pub fn remove(record_id: i32, source: Source)
+    {
+        let _ = db::exec("DELETE FROM record_sources WHERE record_id=? AND source=?", &[&record_id.to_string(), &source.key()]);
+    }
+
+    /* Every source linked to the run; runs imported before provenance was tracked only have their source ids */
+    pub fn for_record(record: &Record) -> Vec<Provenance>
+    {
+        let mut sources: Vec<Provenance> = Vec::new();
+        for &(source, ref source_id) in [(Source::DeerTier, if record.dt_id != 0 { record.dt_id.to_string() } else { String::new() }), (Source::Src, record.src_id.clone())].iter()
+        {
+            if source_id == ""
+            {
+                continue;
+            }
+
+            let stored = db::query("SELECT * FROM record_sources WHERE record_id=? AND source=?", &[&record.id.to_string(), &source.key()]).ok().and_then(|rs| rs.into_iter().next());
+            sources.push(Provenance
+            {
+                source: source,
+                source_id: source_id.clone(),
+                matched_by: stored.as_ref().map(|s| s["matched_by"].clone()).unwrap_or(String::new()),
+                last_seen: stored.as_ref().and_then(|s| s["last_seen"].parse().ok())
+            });
+        }
+        sources
+    }
+
+    pub fn describe(&self) -> String
+    {
+        let mut text = format!("{} {}", self.source, self.source_id);
+        match self.matched_by.as_str()
+        {
+            "runner" | "video" | "comment" => text.push_str(&format!(", merged by {}", self.matched_by)),
+            _ => ()
+        }
+        if let Some(seen) = self.last_seen
+        {
+            text.push_str(&format!(", seen {} ago", SyncState::age_str(Utc::now().timestamp() - seen)));
+        }
+        text
+    }
+}
+
+// A run the two sources disagree about
+pub enum Conflict
+{
+    /* The same video with a different time on each site */
+    Time(Record, Record),
+    /* The same video in a different region on each site */
+    Region(Record, Record),
+    /* A PB of a runner known to both sites that only one of them lists */
+    MissingPb(Record, Source)
+}
+
+impl Conflict
+{
+    pub fn find(category: Option<Category>) -> Result<Vec<Conflict>, Box<::std::error::Error>>
+    {
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        let category_filter = category.map(|c| c.to_db()).unwrap_or(String::new());
+
+        let pairs = db::query("SELECT a.id AS id, CAST(b.id AS text) AS other_id FROM records a JOIN records b ON a.video=b.video AND a.category=b.category AND a.id<b.id
+                               WHERE a.video!='' AND a.status='0' AND b.status='0' AND ((a.dt_id!='0' AND b.src_id!='') OR (a.src_id!='' AND b.dt_id!='0')) AND (?='' OR a.category=?)", &[&category_filter, &category_filter])?;
+        for pair in pairs
+        {
+            let a = Record::from_id(pair["id"].parse()?)?;
+            let b = Record::from_id(pair["other_id"].parse()?)?;
+            if a.region != b.region
+            {
+                conflicts.push(Conflict::Region(a, b));
+            } else if a.realtime != b.realtime {
+                conflicts.push(Conflict::Time(a, b));
+            }
+        }
+
+        let single = db::query("SELECT records.* FROM records JOIN runners ON runners.id=CAST(records.runner_id AS integer)
+                                WHERE records.active=1 AND records.status='0' AND runners.dt_id!='' AND runners.src_id!='' AND ((records.dt_id='0' AND records.src_id!='') OR (records.dt_id!='0' AND records.src_id='')) AND (?='' OR records.category=?)", &[&category_filter, &category_filter])?;
+        for r in single
+        {
+            let record = Record::from_db(&r);
+
+            /* Only categories both sites track can be missing from one of them */
+            let tracked = record.category.definition().map(|d| d.deertier.len() > 0 && d.src.len() > 0).unwrap_or(false);
+            if tracked
+            {
+                let missing = if record.dt_id == 0 { Source::DeerTier } else { Source::Src };
+                conflicts.push(Conflict::MissingPb(record, missing));
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    pub fn line(&self) -> String
+    {
+        match *self
+        {
+            Conflict::Time(ref a, ref b) => format!("**Time** {} {} :: {} has {} on {} and {} on {}", a.category, a.region, a.runner_names(), a.realtime_str(), Conflict::sources(a), b.realtime_str(), Conflict::sources(b)),
+            Conflict::Region(ref a, ref b) => format!("**Region** {} {} :: {} is {} on {} and {} on {}", a.category, a.realtime_str(), a.runner_names(), a.region, Conflict::sources(a), b.region, Conflict::sources(b)),
+            Conflict::MissingPb(ref r, missing) =>
+            {
+                let name = Runner::from_id(r.runner_id).map(|r| r.name).unwrap_or(String::from("?"));
+                format!("**Missing** {} {} :: {}'s PB of {} is not on {}", r.category, r.region, name, r.realtime_str(), missing)
+            }
+        }
+    }
+
+    fn sources(record: &Record) -> String
+    {
+        let names: Vec<String> = Provenance::for_record(record).iter().map(|p| p.source.to_string()).collect();
+        names.join("+")
+    }
+}
\ No newline at end of file
@@ -1,171 +1,108 @@
 use api::deertier;
-use api::src;
 use std::io::{Error, ErrorKind};
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use time;
 use num;
 use db;
 
-// This implements the records as stored internally
-#[derive(Copy, Clone, FromPrimitive, PartialEq)]
-pub enum Category
+pub use models::categories::{Category, Timing};
+
+// How a run was matched to a stored one when it was saved
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MatchKind
 {
-    AnyPercent,
-    AnyPercentGlitched,
-    AnyPercentGTCode,
-    OneHundredPercent,
-    LowPercentIce,
-    LowPercentSpeed,
-    LowPercentXIce,
-    LowPercentSpeedBoots,
-    LowPercentIceBoots,
-    LowPercentIceBooster,
-    LowPercentAllBosses,
-    LowPercentGlitched,
-    RBO,
-    GTClassic,
-    CeresEscape,
-    MapCompletion,
-    SporeSpawnRTA,
-    BotwoonRTA,
-    CrocomireRTA,
-    Unknown
+    Id,
+    Runner,
+    Video,
+    Comment,
+    New
 }
 
-impl Category
+impl MatchKind
 {
-    pub fn from_name(category: &str) -> Category
-    {
-        match category.to_lowercase().as_str()
-        {
-            "any%" => Category::AnyPercent,
-            "100%" => Category::OneHundredPercent,
-            "100% map" => Category::MapCompletion,
-            "map completion" => Category::MapCompletion,
-            "low% ice" => Category::LowPercentIce,
-            "14% ice" => Category::LowPercentIce,
-            "low% speed" => Category::LowPercentSpeed,
-            "14% speed" => Category::LowPercentSpeed,
-            "rbo" => Category::RBO,
-            "ceres" => Category::CeresEscape,
-            "ssrta" => Category::SporeSpawnRTA,
-            "any% glitched" => Category::AnyPercentGlitched,
-            "any% gt" => Category::AnyPercentGTCode,
-            "any% gt code" => Category::AnyPercentGTCode,
-            "gt classic" => Category::GTClassic,
-            "low% glitched" => Category::LowPercentGlitched,
-            "3%" => Category::LowPercentGlitched,
-            "0%" => Category::LowPercentGlitched,
-            "low% all bosses" => Category::LowPercentAllBosses,
-            "12%" => Category::LowPercentAllBosses,
-            "any% pal" => Category::AnyPercent,
-            "low% iceboots" => Category::LowPercentIceBoots,
-            "14% iceboots" => Category::LowPercentIceBoots,
-            "low% speedboots" => Category::LowPercentSpeedBoots,
-            "14% speedboots" => Category::LowPercentSpeedBoots,
-            "low% icebooster" => Category::LowPercentIceBooster,
-            "14% icebooster" => Category::LowPercentIceBooster,
-            "low% xice" => Category::LowPercentXIce,
-            "low% x-ice" => Category::LowPercentXIce,
-            "14% xice" => Category::LowPercentXIce,
-            "14% x-ice" => Category::LowPercentXIce,
-            "crocomire rta" => Category::CrocomireRTA,
-            _ => Category::Unknown       
-        }
-    }
-
-    pub fn from_dt_category(category: &str) -> Category
-    {
-        match category
-        {
-            "AnyPercentRealTime" => Category::AnyPercent,
-            "OneHundredPercent" => Category::OneHundredPercent,
-            "OneHundredPercentMap" => Category::MapCompletion,
-            "LowPercentIce" => Category::LowPercentIce,
-            "LowPercentSpeed" => Category::LowPercentSpeed,
-            "RBO" => Category::RBO,
-            "Ceres" => Category::CeresEscape,
-            "SporeSpawnRTA" => Category::SporeSpawnRTA,
-            "AnyPercentGlitched" => Category::AnyPercentGlitched,
-            "AnyPercentGTCode" => Category::AnyPercentGTCode,
-            "GTClassic" => Category::GTClassic,
-            "LowPercentGlitched" => Category::LowPercentGlitched,
-            "LowPercentAllBosses" => Category::LowPercentAllBosses,
-            "PALAnyPercentRealTime" => Category::AnyPercent,
-            "PALLowPercentIceboots" => Category::LowPercentIceBoots,
-            "PALLowPercentSpeedBoots" => Category::LowPercentSpeedBoots,
-            "LowPercentIceBooster" => Category::LowPercentIceBooster,
-            "LowPercentXIce" => Category::LowPercentXIce,
-            "BotwoonRTA" => Category::BotwoonRTA,
-            "CrocomireRTA" => Category::CrocomireRTA,
-            _ => Category::Unknown
-        }
-    }
-    
-    pub fn to_db(&self) -> String
+    pub fn to_db(self) -> String
     {
-        let value = *self as i32;
-        value.to_string()
+        String::from(match self
+        {
+            MatchKind::Id => "id",
+            MatchKind::Runner => "runner",
+            MatchKind::Video => "video",
+            MatchKind::Comment => "comment",
+            MatchKind::New => "new"
+        })
     }
 }
 
-impl fmt::Display for Category
+// A run whose active flag differs from what rebuild_active computed
+pub struct ActiveChange
 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
-    {
-        match *self
-        {
-            Category::AnyPercent => write!(f, "Any%"),
-            Category::AnyPercentGlitched => write!(f, "Any% Glitched"),
-            Category::AnyPercentGTCode => write!(f, "Any% GT Code"),
-            Category::BotwoonRTA => write!(f, "Botwoon RTA"),
-            Category::CeresEscape => write!(f, "Ceres Escape"),
-            Category::CrocomireRTA => write!(f, "Crocomire RTA"),
-            Category::GTClassic => write!(f, "GT Classic"),
-            Category::LowPercentAllBosses => write!(f, "Low% Glitched All Bosses"),
-            Category::LowPercentGlitched => write!(f, "Low% Glitched"),
-            Category::LowPercentIce => write!(f, "14% Ice"),
-            Category::LowPercentIceBooster => write!(f, "14% IceBooster"),
-            Category::LowPercentIceBoots => write!(f, "14% IceBoots"),
-            Category::LowPercentSpeed => write!(f, "14% Speed"),
-            Category::LowPercentSpeedBoots => write!(f, "14% SpeedBoots"),
-            Category::LowPercentXIce => write!(f, "14% X-Ice"),
-            Category::MapCompletion => write!(f, "Map Completion"),
-            Category::OneHundredPercent => write!(f, "100%"),
-            Category::RBO => write!(f, "RBO"),
-            Category::SporeSpawnRTA => write!(f, "Spore Spawn RTA"),
-            Category::Unknown => write!(f, "Unknown"),
-            _ => write!(f, "Invalid category")
-        }
-    }
+    pub record_id: i32,
+    pub runner_id: i32,
+    pub category: Category,
+    pub region: Region,
+    pub realtime: i32,
+    pub active: i32
 }
 
-#[derive(Debug, Copy, Clone, FromPrimitive, PartialEq)]
+#[derive(Debug, Copy, Clone, FromPrimitive, PartialEq, Deserialize)]
 pub enum Region
 {
     NTSC,
     PAL
 }
 
+impl Default for Region
+{
+    fn default() -> Region
+    {
+        Region::NTSC
+    }
+}
+
 impl Region
 {
     pub fn from_dt_category(category: &str) -> Region
     {
-        match category
+        match Category::from_dt_category(category).definition()
         {
-            "PALAnyPercentRealTime" => Region::PAL,
-            "PALLowPercentIceboots" => Region::PAL,
-            "PALLowPercentSpeedBoots" => Region::PAL,
-            _ => Region::NTSC
+            Some(d) => d.deertier.iter().find(|m| m.key == category).and_then(|m| m.region).unwrap_or(d.region),
+            None => Region::NTSC
         }
     }
 
-    pub fn to_db(&self) -> String
+    pub fn to_db(self) -> String
     {
-        let value = *self as i32;
+        let value = self as i32;
         value.to_string()
     }
+
+    pub fn from_name(region: &str) -> Option<Region>
+    {
+        match region.to_lowercase().as_str()
+        {
+            "ntsc" => Some(Region::NTSC),
+            "pal" => Some(Region::PAL),
+            _ => None
+        }
+    }
+
+    pub fn all() -> Vec<Region>
+    {
+        vec![Region::NTSC, Region::PAL]
+    }
+}
+
+impl fmt::Display for Region
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Region::NTSC => write!(f, "NTSC"),
+            Region::PAL => write!(f, "PAL")
+        }
+    }
 }
 
 pub struct Runner
@@ -247,13 +184,55 @@ impl Runner
         }
     }
 
+    /* A runner with the name that has nothing in the given id column yet */
+    pub fn from_unlinked_name(name: &String, column: &'static str) -> Option<Runner>
+    {
+        match db::query(&format!("SELECT * FROM runners WHERE name=? COLLATE NOCASE AND IFNULL({}, '')='' ORDER BY id ASC", column), &[&name.as_str()])
+        {
+            Ok(records) => records.first().map(Runner::from_db),
+            Err(_) => None
+        }
+    }
+
+    /* Whether the runner has a DeerTier or SRC account that differs from the given ones */
+    pub fn conflicts(&self, dt_id: &str, src_id: &str) -> bool
+    {
+        (self.dt_id != "" && dt_id != "" && self.dt_id != dt_id) || (self.src_id != "" && src_id != "" && self.src_id != src_id)
+    }
+
+    /* Moves the runner's accounts and runs over to another runner and deletes it, then recomputes the PBs that were affected */
+    pub fn merge_into(&self, target: &mut Runner)
+    {
+        if self.id == target.id
+        {
+            return;
+        }
+
+        info!("Merging runner {} ({}) into {} ({})", self.id, self.name, target.id, target.name);
+        if target.dt_id == "" { target.dt_id = self.dt_id.clone(); }
+        if target.src_id == "" { target.src_id = self.src_id.clone(); }
+
+        let boards = db::query("SELECT DISTINCT category, region FROM records WHERE runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)", &[&self.id.to_string(), &self.id.to_string()]).unwrap_or(Vec::new());
+        let _ = db::exec("UPDATE records SET runner_id=? WHERE runner_id=?", &[&target.id.to_string(), &self.id.to_string()]);
+        let _ = db::exec("UPDATE OR IGNORE record_runners SET runner_id=? WHERE runner_id=?", &[&target.id.to_string(), &self.id.to_string()]);
+        let _ = db::exec("DELETE FROM record_runners WHERE runner_id=?", &[&self.id.to_string()]);
+        let _ = db::exec("DELETE FROM runners WHERE id=?", &[&self.id.to_string()]);
+        target.save();
+
+        for board in boards
+        {
+            let region: Region = num::FromPrimitive::from_i32(board["region"].parse::<i32>().unwrap_or(0)).unwrap_or(Region::NTSC);
+            Record::update_pb(target.id, Category::from_db(&board["category"]), region);
+        }
+    }
+
     pub fn save(&mut self)
     {
         if self.id != 0
         {
-            let result = db::exec("UPDATE runners SET name=?,dt_id=?,src_id=?,sync=? WHERE id=?", &[&self.name, &self.dt_id, &self.src_id, &self.sync.to_string(), &self.id.to_string()]);
+            let _ = db::exec("UPDATE runners SET name=?,dt_id=?,src_id=?,sync=? WHERE id=?", &[&self.name, &self.dt_id, &self.src_id, &self.sync.to_string(), &self.id.to_string()]);
         } else {
-            let result = db::exec("INSERT INTO runners (name, dt_id, src_id, sync) VALUES (?, ?, ?, ?)", &[&self.name, &self.dt_id, &self.src_id, &self.sync.to_string()]);
+            let _ = db::exec("INSERT INTO runners (name, dt_id, src_id, sync) VALUES (?, ?, ?, ?)", &[&self.name, &self.dt_id, &self.src_id, &self.sync.to_string()]);
             if let Ok(ids) = db::query("SELECT seq AS id FROM sqlite_sequence WHERE name='runners'", &[])
             {
                 if let Some(id) = ids.first()
@@ -265,12 +244,82 @@ impl Runner
     }
 }
 
+// A runner credited by a source, matched against the stored runners without writing anything.
+// The runner is only created, or has the account linked, when the run is saved.
+#[derive(Debug, Clone)]
+pub struct RunnerRef
+{
+    /* The stored runner it matched, 0 when there is none yet */
+    pub id: i32,
+    pub name: String,
+    pub dt_id: String,
+    pub src_id: String
+}
+
+impl RunnerRef
+{
+    pub fn dt_user(username: &String) -> RunnerRef
+    {
+        RunnerRef::new(username.clone(), username.clone(), String::new())
+    }
+
+    pub fn src_user(src_id: &String, name: &String) -> RunnerRef
+    {
+        RunnerRef::new(name.clone(), String::new(), src_id.clone())
+    }
+
+    /* Runners without an account, from SRC guests or anywhere else we only have a name, are matched by name */
+    pub fn guest(name: &String) -> RunnerRef
+    {
+        RunnerRef::new(name.clone(), String::new(), String::new())
+    }
+
+    fn new(name: String, dt_id: String, src_id: String) -> RunnerRef
+    {
+        let mut runner = RunnerRef { id: 0, name: name, dt_id: dt_id, src_id: src_id };
+        runner.id = runner.lookup().map(|r| r.id).unwrap_or(0);
+        runner
+    }
+
+    /* A runner with the same name is only taken to be the same person while no other account of that source is linked to them */
+    fn lookup(&self) -> Option<Runner>
+    {
+        if self.src_id != ""
+        {
+            Runner::from_src_id(&self.src_id).ok().or_else(|| Runner::from_unlinked_name(&self.name, "src_id"))
+        } else if self.dt_id != "" {
+            Runner::from_dt_id(&self.dt_id).ok().or_else(|| Runner::from_unlinked_name(&self.name, "dt_id"))
+        } else {
+            Runner::from_name(&self.name).ok()
+        }
+    }
+
+    /* Creates the runner, or links the account to the runner it matched */
+    pub fn resolve(&self) -> Runner
+    {
+        /* An earlier run in the same change set may have created the runner since it was looked up */
+        let found = if self.id != 0 { Runner::from_id(self.id).ok() } else { self.lookup() };
+        let mut runner = found.unwrap_or(Runner::new(self.name.clone(), String::from(""), String::from(""), 1));
+
+        let changed = runner.id == 0 || (runner.dt_id == "" && self.dt_id != "") || (runner.src_id == "" && self.src_id != "");
+        if runner.dt_id == "" { runner.dt_id = self.dt_id.clone(); }
+        if runner.src_id == "" { runner.src_id = self.src_id.clone(); }
+        if changed
+        {
+            runner.save();
+        }
+        runner
+    }
+}
+
+#[derive(Clone)]
 pub struct Record
 {
     pub id: i32,
     pub dt_id: i32,
     pub src_id: String,
     pub runner_id: i32,
+    pub runners: Vec<i32>,
     pub category: Category,
     pub region: Region,
     pub realtime: i32,
@@ -278,28 +327,14 @@ pub struct Record
     pub comment: String,
     pub video: String,
     pub active: i32,
+    pub date: String,
+    pub weblink: String,
+    /* The runners as the source credited them, resolved into runners when the run is saved */
+    pub credits: Vec<RunnerRef>
 }
 
 impl Record
 {
-    pub fn new(dt_id: i32, src_id: String, runner: &Runner, category: Category, region: Region, realtime: i32, gametime: i32, comment: String, video: String, active: i32) -> Record
-    {
-        Record
-        {
-            id: 0,
-            dt_id: dt_id,
-            src_id: src_id,
-            runner_id: runner.id,
-            category: category,
-            region: region,
-            realtime: realtime,
-            gametime: gametime,
-            comment: comment,
-            video: video,
-            active: active
-        }
-    }
-
     pub fn from_db(record: &HashMap<String, String>) -> Record
     {
         Record
@@ -308,16 +343,31 @@ impl Record
             dt_id: record["dt_id"].parse().unwrap(),
             src_id: record["src_id"].clone(),
             runner_id: record["runner_id"].parse().unwrap(),
-            category: num::FromPrimitive::from_i32(record["category"].parse::<i32>().unwrap()).unwrap(),
+            runners: vec![record["runner_id"].parse().unwrap()],
+            category: Category::from_db(&record["category"]),
             region: num::FromPrimitive::from_i32(record["region"].parse::<i32>().unwrap()).unwrap(),
             realtime: record["realtime"].parse().unwrap(),
             gametime: record["gametime"].parse().unwrap(),
             comment: record["comment"].clone(),
             video: record["video"].clone(),
-            active: record["active"].parse().unwrap()
+            active: record["active"].parse().unwrap(),
+            date: record["date"].clone(),
+            weblink: record["weblink"].clone(),
+            credits: Vec::new()
         }
     }
     
+    pub fn from_id(id: i32) -> Result<Record, Box<::std::error::Error>>
+    {
+        let records = db::query("SELECT * FROM records WHERE id=?", &[&id.to_string()])?;
+        if let Some(record) = records.first()
+        {
+            Ok(Record::from_db(record))
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
+        }
+    }
+
     pub fn from_dt_id(dt_id: i32) -> Result<Record, Box<::std::error::Error>>
     {
         let records = db::query("SELECT * FROM records WHERE dt_id=?", &[&dt_id.to_string()])?;
@@ -349,29 +399,7 @@ impl Record
         }
 
         /* No existing records found, create a new one */        
-        let runner = 
-            match Runner::from_dt_id(&record.username)
-            {
-                Ok(r) => r,
-                _ => 
-                {
-                    match Runner::from_name(&record.username)
-                    {
-                        Ok(mut r) =>
-                        {
-                            r.dt_id = record.username.clone();
-                            r.save();
-                            r
-                        },
-                        _ => 
-                        {
-                            let mut new_runner = Runner::new(record.username.clone(), record.username.clone(), String::from(""), 1);
-                            new_runner.save();
-                            new_runner
-                        }
-                    }
-                }
-            };
+        let runner = RunnerRef::dt_user(&record.username);
 
         Record
         {
@@ -379,114 +407,178 @@ impl Record
             dt_id: record.id,
             src_id: String::from(""),
             runner_id: runner.id,
+            runners: vec![runner.id],
             category: Category::from_dt_category(record.category.as_ref()),
             region: Region::from_dt_category(record.category.as_ref()),
             realtime: Record::convert_dt_time(&record.real_time),
             gametime: Record::convert_dt_time(&record.game_time) * 60,
             comment: record.comment.as_ref().cloned().unwrap_or(String::from("")),
             video: record.video_url.as_ref().cloned().unwrap_or(String::from("")),
-            active: 1        
+            active: 1,
+            date: record.date_submitted.as_ref().map(|d| d.chars().take(10).collect()).unwrap_or(String::from("")),
+            weblink: String::from(""),
+            credits: vec![runner]
         }
     }
 
-    pub fn save(&mut self)
+    /* Returns how the run was matched to the stored one, so the caller can record where it came from */
+    pub fn save(&mut self) -> MatchKind
     {
-        if self.id != 0
+        let kind = self.save_record();
+        self.save_runners();
+
+        if kind != MatchKind::Id && kind != MatchKind::Runner
         {
-            let result = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, active=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &self.comment, &self.video, &self.active.to_string(), &self.id.to_string()]);
-        } else {
+            /* A new run, or one whose runners were merged, can be the PB of anyone credited with it */
+            for runner_id in self.runners.clone()
+            {
+                Record::update_pb(runner_id, self.category, self.region);
+            }
+            if let Ok(r) = Record::from_id(self.id)
+            {
+                self.active = r.active;
+            }
+        }
+        kind
+    }
 
-            /* Before inserting, try to find this record in the database */
-            let records = db::query("SELECT * FROM records WHERE runner_id=? AND category=? AND region=? AND realtime=?", &[&self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()]);
+    /* Links every runner of the run to the record, the first runner is also kept in records.runner_id */
+    fn save_runners(&mut self)
+    {
+        if self.id == 0
+        {
+            return;
+        }
+
+        if !self.runners.contains(&self.runner_id)
+        {
+            self.runners.insert(0, self.runner_id);
+        }
+
+        for runner_id in self.runners.iter()
+        {
+            let _ = db::exec("INSERT OR IGNORE INTO record_runners (record_id, runner_id) VALUES (?, ?)", &[&self.id.to_string(), &runner_id.to_string()]);
+        }
+    }
+
+    /* Whether the stored run's runner has a DeerTier or SRC account other than the one this run comes from,
+       in which case a matching video or comment does not make them the same person */
+    fn conflicts_with(&self, stored: &Record) -> bool
+    {
+        let (mut dt_id, mut src_id) = Runner::from_id(self.runner_id).map(|r| (r.dt_id, r.src_id)).unwrap_or((String::new(), String::new()));
+        if let Some(c) = self.credits.first()
+        {
+            if dt_id == "" { dt_id = c.dt_id.clone(); }
+            if src_id == "" { src_id = c.src_id.clone(); }
+        }
+
+        match Runner::from_id(stored.runner_id)
+        {
+            Ok(existing) => existing.conflicts(&dt_id, &src_id),
+            Err(_) => false
+        }
+    }
+
+    /* Creates or links the runners the source credited, now that the run is being written */
+    fn resolve_runners(&mut self)
+    {
+        if self.credits.len() == 0
+        {
+            return;
+        }
+
+        self.runners = self.credits.iter().map(|c| c.resolve().id).collect();
+        self.runner_id = self.runners[0];
+        self.credits.clear();
+    }
 
-            if let Ok(rs) = records
+    /* Looks for a stored run that this unsaved run duplicates, first by runner, then by video and finally by comment */
+    pub fn find_existing(&self) -> Option<(Record, MatchKind)>
+    {
+        if let Ok(rs) = db::query("SELECT * FROM records WHERE runner_id=? AND category=? AND region=? AND realtime=?", &[&self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()])
+        {
+            if let Some(r) = rs.first()
             {
-                if let Some(r) = rs.first()
+                return Some((Record::from_db(r), MatchKind::Runner));
+            }
+        }
+
+        if self.video != ""
+        {
+            if let Ok(rs) = db::query("SELECT * FROM records WHERE video=? AND category=? AND region=? AND realtime=?", &[&self.video.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()])
+            {
+                if let Some(r) = rs.iter().map(Record::from_db).find(|r| !self.conflicts_with(r))
                 {
-                    let record = Record::from_db(r);
-                    self.id = record.id;
-                    self.active = record.active;
-                    if self.dt_id == 0 { self.dt_id = record.dt_id; };
-                    if self.src_id == "" { self.src_id = record.src_id; };
-                    let _ = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, active=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &record.comment, &record.video, &self.active.to_string(), &self.id.to_string()]);
-                    return;
+                    return Some((r, MatchKind::Video));
                 }
-            }             
+            }
+        }
 
-            /* Try again, matching on video instead of runner */
-            if self.video != ""
+        if self.comment != ""
+        {
+            if let Ok(rs) = db::query("SELECT * FROM records WHERE comment=? AND category=? AND region=? AND realtime=?", &[&self.comment.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()])
             {
-                let records = db::query("SELECT * FROM records WHERE video=? AND category=? AND region=? AND realtime=?", &[&self.video.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()]);
-                if let Ok(rs) = records
+                if let Some(r) = rs.iter().map(Record::from_db).find(|r| !self.conflicts_with(r))
                 {
-                    if let Some(r) = rs.first()
-                    {
-                        let record = Record::from_db(r);
-
-                        /* First update the runner with the new missing data */
-                        let old_runner = Runner::from_id(self.runner_id).unwrap();
-                        let mut new_runner = Runner::from_id(record.runner_id).unwrap();
-                        if new_runner.dt_id == "" { new_runner.dt_id = old_runner.dt_id };
-                        if new_runner.src_id == "" { new_runner.src_id = old_runner.src_id };
-                        new_runner.save();
-
-                        self.id = record.id;
-                        self.runner_id = new_runner.id;
-                        self.active = record.active;
-                        if self.dt_id == 0 { self.dt_id = record.dt_id; };
-                        if self.src_id == "" { self.src_id = record.src_id; };
-                        let _ = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, active=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &record.comment, &record.video, &self.active.to_string(), &self.id.to_string()]);
-                        
-                        return;
-                    }
+                    return Some((r, MatchKind::Comment));
                 }
-            } 
+            }
+        }
+
+        None
+    }
+
+    fn save_record(&mut self) -> MatchKind
+    {
+        if self.id != 0
+        {
+            /* The active flag is left alone, it is maintained by update_pb */
+            let _ = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, date=?, weblink=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &self.comment, &self.video, &self.date, &self.weblink, &self.id.to_string()]);
+            MatchKind::Id
+        } else {
 
-            /* Try again, matching on comment instead of runner */
-            if self.comment != ""
+            /* Before inserting, try to find this record in the database */
+            let existing = self.find_existing();
+
+            /* A runner that does not exist yet takes over the stored run's runner instead of being created */
+            if let Some((ref record, kind)) = existing
             {
-                let records = db::query("SELECT * FROM records WHERE comment=? AND category=? AND region=? AND realtime=?", &[&self.comment.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()]);
-                if let Ok(rs) = records
+                if kind != MatchKind::Runner && self.runner_id == 0
                 {
-                    if let Some(r) = rs.first()
+                    if let Some(c) = self.credits.first_mut()
                     {
-                        let record = Record::from_db(r);
-
-                        /* First update the runner with the new missing data */
-                        let old_runner = Runner::from_id(self.runner_id).unwrap();
-                        let mut new_runner = Runner::from_id(record.runner_id).unwrap();
-                        if new_runner.dt_id == "" { new_runner.dt_id = old_runner.dt_id };
-                        if new_runner.src_id == "" { new_runner.src_id = old_runner.src_id };
-                        new_runner.save();
-
-                        self.id = record.id;
-                        self.runner_id = new_runner.id;
-                        self.active = record.active;
-                        if self.dt_id == 0 { self.dt_id = record.dt_id; };
-                        if self.src_id == "" { self.src_id = record.src_id; };
-                        let _ = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, active=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &record.comment, &record.video, &self.active.to_string(), &self.id.to_string()]);
-                        
-                        return;
+                        c.id = record.runner_id;
                     }
                 }
-            }           
+            }
+            self.resolve_runners();
 
-            /* Is this the best run for this player and category? */
-            let records = db::query("SELECT * FROM records WHERE runner_id=? AND category=? AND region=? AND CAST(realtime as INTEGER)<CAST(? as INTEGER)", &[&self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()]);
-            if let Ok(rs) = records
+            if let Some((record, kind)) = existing
             {
-                if let Some(_r) = rs.first()
+                if kind != MatchKind::Runner && record.runner_id != self.runner_id
                 {
-                    self.active = 0;
+                    /* Same run under another runner, so both runners are the same person: fold the incoming one into the stored one */
+                    if let (Ok(old_runner), Ok(mut new_runner)) = (Runner::from_id(self.runner_id), Runner::from_id(record.runner_id))
+                    {
+                        old_runner.merge_into(&mut new_runner);
+                        self.runners = self.runners.iter().map(|r| if *r == old_runner.id { new_runner.id } else { *r }).collect();
+                    }
+                    self.runner_id = record.runner_id;
                 }
-            }
-            
-            if self.active == 1
-            {
-                let _ = db::exec("UPDATE records SET active=0 WHERE runner_id=? AND category=? AND region=? AND CAST(realtime as INTEGER)>CAST(? as INTEGER)", &[&self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string()]);
+
+                self.id = record.id;
+                self.active = record.active;
+                if self.dt_id == 0 { self.dt_id = record.dt_id; };
+                if self.src_id == "" { self.src_id = record.src_id; };
+                if self.date == "" { self.date = record.date; };
+                if self.weblink == "" { self.weblink = record.weblink; };
+                let _ = db::exec("UPDATE records SET dt_id=?, src_id=?, runner_id=?, category=?, region=?, realtime=?, gametime=?, comment=?, video=?, active=?, date=?, weblink=? WHERE id=?", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &record.comment, &record.video, &self.active.to_string(), &self.date, &self.weblink, &self.id.to_string()]);
+                return kind;
             }
 
-            let _ = db::exec("INSERT INTO records (dt_id, src_id, runner_id, category, region, realtime, gametime, comment, video, active, status, sync_status) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, '0', '0')", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &self.comment, &self.video, &self.active.to_string()]);
+            /* Inserted inactive, save works out whose PB it is once the runners are linked */
+            self.active = 0;
+            let _ = db::exec("INSERT INTO records (dt_id, src_id, runner_id, category, region, realtime, gametime, comment, video, active, status, sync_status, date, weblink) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, '0', '0', ?, ?)", &[&self.dt_id.to_string(), &self.src_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime.to_string(), &self.gametime.to_string(), &self.comment, &self.video, &self.active.to_string(), &self.date, &self.weblink]);
             if let Ok(ids) = db::query("SELECT seq AS id FROM sqlite_sequence WHERE name='records'", &[])
             {
                 if let Some(id) = ids.first()
@@ -495,26 +587,65 @@ impl Record
                 }
             }
 
+            MatchKind::New
         }      
     }
 
-    pub fn get_rank(&self) -> i32
+    pub fn get_rank(&self) -> Result<i32, Box<::std::error::Error>>
     {
-        let mut rank: i32 = 999;
-        if let Ok(records) = db::query("SELECT COUNT(*) as id FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 AND CAST(realtime AS integer)<=CAST(? AS integer) ORDER BY CAST(realtime AS integer) ASC", &[&self.category.to_db(), &self.region.to_db(), &self.realtime])
+        if self.active != 1 || self.realtime == 0
         {
-            if let Some(record) = records.first()
-            {
-                rank = record["id"].parse().unwrap();
-            }
+            return Err(Box::new(Error::new(ErrorKind::InvalidInput, "Only active, timed records have a rank")));
+        }
+
+        /* Tied runs share the better rank, the same as rank_for_time and the overall ranking */
+        let records = db::query("SELECT COUNT(*) as id FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 AND CAST(realtime AS integer)<CAST(? AS integer)", &[&self.category.to_db(), &self.region.to_db(), &self.realtime])?;
+        if let Some(record) = records.first()
+        {
+            Ok(record["id"].parse::<i32>()? + 1)
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
+        }
+    }
+
+    /* Number of active, timed runs on the same board */
+    pub fn get_board_size(&self) -> Result<i32, Box<::std::error::Error>>
+    {
+        let records = db::query("SELECT COUNT(*) as id FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0", &[&self.category.to_db(), &self.region.to_db()])?;
+        if let Some(record) = records.first()
+        {
+            Ok(record["id"].parse()?)
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
         }
-        rank
     }
 
-    pub fn get_top(category: Category) -> Result<Vec<Record>, Box<::std::error::Error>>
+    /* The closest faster and slower active runs on the same board that share no runner with this one */
+    pub fn get_neighbours(&self) -> Result<(Option<Record>, Option<Record>), Box<::std::error::Error>>
+    {
+        let others = "runner_id!=? AND runner_id NOT IN (SELECT runner_id FROM record_runners WHERE record_id=?) AND id NOT IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=? OR runner_id IN (SELECT runner_id FROM record_runners WHERE record_id=?))";
+        let (id, runner_id) = (self.id.to_string(), self.runner_id.to_string());
+        let above = db::query(&format!("SELECT * FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 AND {} AND CAST(realtime AS integer)<CAST(? AS integer) ORDER BY CAST(realtime AS integer) DESC LIMIT 1", others), &[&self.category.to_db(), &self.region.to_db(), &runner_id, &id, &runner_id, &id, &self.realtime])?;
+        let below = db::query(&format!("SELECT * FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 AND {} AND CAST(realtime AS integer)>=CAST(? AS integer) ORDER BY CAST(realtime AS integer) ASC LIMIT 1", others), &[&self.category.to_db(), &self.region.to_db(), &runner_id, &id, &runner_id, &id, &self.realtime])?;
+        Ok((above.first().map(Record::from_db), below.first().map(Record::from_db)))
+    }
+
+    /* The runner's best inactive run that is slower than this one, i.e. the PB this run replaced */
+    pub fn get_previous_pb(&self) -> Result<Record, Box<::std::error::Error>>
+    {
+        let record = db::query("SELECT * FROM records WHERE (runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)) AND category=? AND region=? AND active=0 AND realtime!=0 AND CAST(realtime AS integer)>CAST(? AS integer) ORDER BY CAST(realtime AS integer) ASC LIMIT 1", &[&self.runner_id.to_string(), &self.runner_id.to_string(), &self.category.to_db(), &self.region.to_db(), &self.realtime])?;
+        if let Some(rec) = record.first()
+        {
+            Ok(Record::from_db(rec))
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
+        }
+    }
+
+    pub fn get_top(category: Category, offset: i32, limit: i32) -> Result<Vec<Record>, Box<::std::error::Error>>
     {
         let mut top: Vec<Record> = Vec::new();
-        let records = db::query("SELECT * FROM records WHERE category=? AND active=1 AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC LIMIT 10", &[&category.to_db()])?;
+        let records = db::query("SELECT * FROM records WHERE category=? AND active=1 AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC LIMIT ? OFFSET ?", &[&category.to_db(), &limit, &offset])?;
         for record in records
         {
             let r = Record::from_db(&record);
@@ -526,21 +657,62 @@ impl Record
     pub fn get_records(runner_id: i32) -> Result<Vec<Record>, Box<::std::error::Error>>
     {
         let mut top: Vec<Record> = Vec::new();
-        let records = db::query("SELECT * FROM records WHERE runner_id=? AND active=1 AND realtime!=0 ORDER BY CAST(category AS integer) ASC", &[&runner_id.to_string()])?;
+        let records = db::query("SELECT * FROM records WHERE (runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)) AND active=1 AND realtime!=0", &[&runner_id.to_string(), &runner_id.to_string()])?;
         for record in records
         {
             let r = Record::from_db(&record);
             top.push(r);
         }
+        top.sort_by_key(|r| r.category);
         Ok(top)      
     }
 
+    /* Every stored run for a runner, including the ones that are no longer their PB */
+    pub fn get_history(runner_id: i32) -> Result<Vec<Record>, Box<::std::error::Error>>
+    {
+        let mut history: Vec<Record> = Vec::new();
+        let records = db::query("SELECT * FROM records WHERE (runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)) AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC", &[&runner_id.to_string(), &runner_id.to_string()])?;
+        for record in records
+        {
+            history.push(Record::from_db(&record));
+        }
+        history.sort_by_key(|r| r.category);
+        Ok(history)
+    }
+
+    /* Every active, timed run on one board, fastest first */
+    pub fn get_board(category: Category, region: Region) -> Result<Vec<Record>, Box<::std::error::Error>>
+    {
+        let mut board: Vec<Record> = Vec::new();
+        let records = db::query("SELECT * FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC", &[&category.to_db(), &region.to_db()])?;
+        for record in records
+        {
+            board.push(Record::from_db(&record));
+        }
+        Ok(board)
+    }
+
+    /* The date each runner first submitted a run on a board, for runners with dated runs */
+    pub fn get_first_dates(category: Category, region: Option<Region>) -> Result<Vec<String>, Box<::std::error::Error>>
+    {
+        /* Co-op partners credited through record_runners count as runners too, the same as in get_records */
+        let runs = "SELECT CAST(runner_id AS integer) AS runner, date, category, region FROM records WHERE date!=''
+            UNION ALL SELECT CAST(record_runners.runner_id AS integer), records.date, records.category, records.region FROM record_runners
+            JOIN records ON records.id=CAST(record_runners.record_id AS integer) WHERE records.date!=''";
+        let records = match region
+        {
+            Some(r) => db::query(&format!("SELECT runner AS id, MIN(date) AS date FROM ({}) WHERE category=? AND region=? GROUP BY runner", runs), &[&category.to_db(), &r.to_db()])?,
+            None => db::query(&format!("SELECT runner AS id, MIN(date) AS date FROM ({}) WHERE category=? GROUP BY runner", runs), &[&category.to_db()])?
+        };
+        Ok(records.iter().map(|r| r["date"].clone()).collect())
+    }
+
     pub fn get_pb(runner_id: i32, category: Category) -> Result<Record, Box<::std::error::Error>>
     {
-        let record = db::query("SELECT * FROM records WHERE runner_id=? AND category=? AND active=1", &[&runner_id.to_string(), &category.to_db()])?;
+        let record = db::query("SELECT * FROM records WHERE (runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)) AND category=? AND active=1", &[&runner_id.to_string(), &runner_id.to_string(), &category.to_db()])?;
         if let Some(rec) = record.first()
         {
-            Ok(Record::from_db(&rec))
+            Ok(Record::from_db(rec))
         } else {
             Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
         }
@@ -551,12 +723,248 @@ impl Record
         let record = db::query("SELECT * FROM records WHERE category=? AND active=1 AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC LIMIT 1", &[&category.to_db()])?;
         if let Some(rec) = record.first()
         {
-            Ok(Record::from_db(&rec))
+            Ok(Record::from_db(rec))
         } else {
             Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
         }
     }
 
+    pub fn get_region_wr(category: Category, region: Region) -> Result<Record, Box<::std::error::Error>>
+    {
+        let record = db::query("SELECT * FROM records WHERE category=? AND region=? AND active=1 AND realtime!=0 ORDER BY CAST(realtime AS integer) ASC LIMIT 1", &[&category.to_db(), &region.to_db()])?;
+        if let Some(rec) = record.first()
+        {
+            Ok(Record::from_db(rec))
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
+        }
+    }
+
+    pub fn mark_seen_src(src_id: &String, timestamp: i64)
+    {
+        let _ = db::exec("UPDATE records SET src_last_seen=? WHERE src_id=?", &[&timestamp.to_string(), &src_id.as_str()]);
+    }
+
+    pub fn mark_seen_dt(dt_id: i32, timestamp: i64)
+    {
+        let _ = db::exec("UPDATE records SET dt_last_seen=? WHERE dt_id=?", &[&timestamp.to_string(), &dt_id.to_string()]);
+    }
+
+    /* All runs linked to SRC, older runs included since one of them becomes the PB again when a newer one goes */
+    pub fn get_linked_src() -> Result<Vec<Record>, Box<::std::error::Error>>
+    {
+        let mut linked: Vec<Record> = Vec::new();
+        let records = db::query("SELECT * FROM records WHERE src_id!='' AND status='0'", &[])?;
+        for record in records
+        {
+            linked.push(Record::from_db(&record));
+        }
+        Ok(linked)
+    }
+
+    /* All runs linked to DeerTier */
+    pub fn get_linked_dt() -> Result<Vec<Record>, Box<::std::error::Error>>
+    {
+        let mut linked: Vec<Record> = Vec::new();
+        let records = db::query("SELECT * FROM records WHERE dt_id!='0' AND status='0'", &[])?;
+        for record in records
+        {
+            linked.push(Record::from_db(&record));
+        }
+        Ok(linked)
+    }
+
+    /* The run currently marked as the runner's PB, solo or shared */
+    pub fn get_active(runner_id: i32, category: Category, region: Region) -> Option<Record>
+    {
+        Record::best_of(Record::get_credited(runner_id, category, region).iter().filter(|r| r.active == 1)).cloned()
+    }
+
+    /* Every run on the board that the runner is credited with, solo or shared */
+    fn get_credited(runner_id: i32, category: Category, region: Region) -> Vec<Record>
+    {
+        match db::query("SELECT * FROM records WHERE (runner_id=? OR id IN (SELECT CAST(record_id AS integer) FROM record_runners WHERE runner_id=?)) AND category=? AND region=? AND status='0'", &[&runner_id.to_string(), &runner_id.to_string(), &category.to_db(), &region.to_db()])
+        {
+            Ok(records) => records.iter().map(Record::from_db).collect(),
+            Err(_) => Vec::new()
+        }
+    }
+
+    /* The ids of everyone credited with the run */
+    fn credited_ids(&self) -> Vec<i32>
+    {
+        let mut ids: Vec<i32> = match db::query("SELECT CAST(runner_id AS integer) AS id FROM record_runners WHERE record_id=?", &[&self.id.to_string()])
+        {
+            Ok(records) => records.iter().filter_map(|r| r["id"].parse().ok()).collect(),
+            Err(_) => Vec::new()
+        };
+        if !ids.contains(&self.runner_id)
+        {
+            ids.insert(0, self.runner_id);
+        }
+        ids
+    }
+
+    pub fn count_active() -> i32
+    {
+        match db::query("SELECT COUNT(*) AS id FROM records WHERE active=1 AND status='0'", &[])
+        {
+            Ok(records) => records.first().and_then(|r| r["id"].parse().ok()).unwrap_or(0),
+            Err(_) => 0
+        }
+    }
+
+    /* Unlinks a source that no longer lists the run; once no source is left the run is removed and the runner's previous best becomes their PB */
+    pub fn drop_source(&mut self, src: bool, dt: bool)
+    {
+        if src { self.src_id = String::new(); }
+        if dt { self.dt_id = 0; }
+
+        if self.src_id == "" && self.dt_id == 0
+        {
+            info!("Removing record {} ({} {} {}) that is no longer listed by any source", self.id, self.category.to_db(), self.region, self.realtime_str());
+            let was_active = self.active == 1;
+            self.active = 0;
+            let _ = db::exec("UPDATE records SET status='1', active='0' WHERE id=?", &[&self.id.to_string()]);
+            self.save();
+            if was_active
+            {
+                for runner_id in self.credited_ids()
+                {
+                    Record::update_pb(runner_id, self.category, self.region);
+                }
+            }
+        } else {
+            self.save();
+        }
+    }
+
+    /* The time a run is ranked by as a PB, using the category's timing method */
+    pub fn pb_time(&self) -> i32
+    {
+        match self.category.timing()
+        {
+            Timing::GameTime if self.gametime > 0 => self.gametime,
+            _ => self.realtime
+        }
+    }
+
+    /* The PB among a runner's runs: the fastest timed run, ties go to the oldest record */
+    pub fn best_of<'a, I>(runs: I) -> Option<&'a Record> where I: Iterator<Item=&'a Record>
+    {
+        runs.filter(|r| r.pb_time() != 0).min_by_key(|r| (r.pb_time(), r.id))
+    }
+
+    /* Marks the runner's best run on the board as active, and clears the flag on their other runs unless one is still a co-runner's PB */
+    pub fn update_pb(runner_id: i32, category: Category, region: Region)
+    {
+        let runs = Record::get_credited(runner_id, category, region);
+        let best = Record::best_of(runs.iter()).map(|r| r.id);
+        for r in runs.iter()
+        {
+            let active = if Some(r.id) == best || r.is_pb_of_other(runner_id) { 1 } else { 0 };
+            if r.active != active
+            {
+                let _ = db::exec("UPDATE records SET active=? WHERE id=?", &[&active.to_string(), &r.id.to_string()]);
+            }
+        }
+    }
+
+    /* Whether the run is the PB of anyone credited with it besides the given runner */
+    fn is_pb_of_other(&self, runner_id: i32) -> bool
+    {
+        self.credited_ids().into_iter().filter(|id| *id != runner_id).any(|other|
+        {
+            Record::best_of(Record::get_credited(other, self.category, self.region).iter()).map(|r| r.id) == Some(self.id)
+        })
+    }
+
+    /* Recomputes which run is the PB for every runner, category and region, timed by the category's timing method.
+       A run is active when it is the PB of any runner credited with it. With apply set the differences are written
+       in the same transaction they were computed in, otherwise they are only reported. */
+    pub fn rebuild_active(apply: bool) -> Result<Vec<ActiveChange>, Box<::std::error::Error>>
+    {
+        let mut changes: Vec<ActiveChange> = Vec::new();
+        db::transaction(|tx|
+        {
+            let records: Vec<Record> = db::query_with(tx, "SELECT * FROM records", &[])?.iter().map(Record::from_db).collect();
+            let status: HashMap<i32, String> = db::query_with(tx, "SELECT id, status FROM records", &[])?.into_iter().map(|r| (r["id"].parse().unwrap_or(0), r["status"].clone())).collect();
+
+            let mut runners: HashMap<i32, Vec<i32>> = HashMap::new();
+            for link in db::query_with(tx, "SELECT CAST(record_id AS integer) AS id, runner_id FROM record_runners", &[])?
+            {
+                runners.entry(link["id"].parse().unwrap_or(0)).or_insert(Vec::new()).push(link["runner_id"].parse().unwrap_or(0));
+            }
+
+            /* Group the runs by everyone credited with them, PBs are picked the same way as update_pb does */
+            let mut boards: HashMap<(i32, String, String), Vec<&Record>> = HashMap::new();
+            for r in records.iter().filter(|r| status.get(&r.id).map(|s| s == "0").unwrap_or(true))
+            {
+                let mut credited = runners.get(&r.id).cloned().unwrap_or(Vec::new());
+                if !credited.contains(&r.runner_id)
+                {
+                    credited.push(r.runner_id);
+                }
+
+                for runner_id in credited
+                {
+                    boards.entry((runner_id, r.category.to_db(), r.region.to_db())).or_insert(Vec::new()).push(r);
+                }
+            }
+
+            let pbs: HashSet<i32> = boards.values().filter_map(|runs| Record::best_of(runs.iter().cloned())).map(|r| r.id).collect();
+            for r in records.iter()
+            {
+                let active = if pbs.contains(&r.id) { 1 } else { 0 };
+                if r.active != active
+                {
+                    changes.push(ActiveChange { record_id: r.id, runner_id: r.runner_id, category: r.category, region: r.region, realtime: r.realtime, active: active });
+                    if apply
+                    {
+                        tx.execute("UPDATE records SET active=? WHERE id=?", &[&active.to_string(), &r.id.to_string()])?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(changes)
+    }
+
+    /* Everyone credited with the run, in the order they were listed */
+    pub fn get_runners(&self) -> Vec<Runner>
+    {
+        let mut runners: Vec<Runner> = Vec::new();
+        if let Ok(records) = db::query("SELECT runners.* FROM record_runners JOIN runners ON runners.id=CAST(record_runners.runner_id AS integer) WHERE record_runners.record_id=? ORDER BY record_runners.rowid ASC", &[&self.id.to_string()])
+        {
+            for record in records
+            {
+                runners.push(Runner::from_db(&record));
+            }
+        }
+
+        if runners.len() == 0
+        {
+            if let Ok(r) = Runner::from_id(self.runner_id)
+            {
+                runners.push(r);
+            }
+        }
+        runners
+    }
+
+    pub fn runner_names(&self) -> String
+    {
+        /* Runs that are not saved yet only have the names the source credited */
+        if self.credits.len() > 0
+        {
+            return self.credits.iter().map(|c| c.name.clone()).collect::<Vec<String>>().join(" & ");
+        }
+
+        let names: Vec<String> = self.get_runners().into_iter().map(|r| r.name).collect();
+        if names.len() > 0 { names.join(" & ") } else { String::from("?") }
+    }
+
     pub fn realtime_str(&self) -> String
     {
         Record::from_seconds(self.realtime as i64)
@@ -567,15 +975,56 @@ impl Record
         Record::from_seconds(self.gametime as i64)
     }
 
-    fn from_seconds(seconds: i64) -> String
+    /* Signed difference between two times, e.g. "+1:05" */
+    pub fn delta_str(seconds: i32) -> String
     {
-        if seconds >= 3600
+        let sign = if seconds < 0 { "-" } else { "+" };
+        let s = seconds.abs();
+        if s >= 3600
         {
-            return time::strftime("%k:%M:%S", &time::at_utc(time::Timespec::new(seconds, 0))).unwrap_or(String::new()).trim().to_string();    
+            format!("{}{}:{:02}:{:02}", sign, s / 3600, (s % 3600) / 60, s % 60)
+        } else {
+            format!("{}{}:{:02}", sign, s / 60, s % 60)
         }
-        else 
+    }
+
+    pub fn timing_str(&self) -> String
+    {
+        self.category.timing().to_string()
+    }
+
+    /* Links to the run on speedrun.com when we have it, otherwise to the DeerTier leaderboard */
+    pub fn source_link(&self) -> String
+    {
+        if self.weblink != ""
+        {
+            self.weblink.clone()
+        } else if self.dt_id != 0 {
+            String::from("https://deertier.com/Leaderboard")
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn rank_str(rank: i32) -> String
+    {
+        match rank
+        {
+            1 => String::from("🥇"),
+            2 => String::from("🥈"),
+            3 => String::from("🥉"),
+            r if r <= 0 => String::from("-"),
+            r => format!("#{}", r)
+        }
+    }
+
+    pub fn from_seconds(seconds: i64) -> String
+    {
+        if seconds >= 3600
         {
-            return time::strftime("%M:%S", &time::at_utc(time::Timespec::new(seconds, 0))).unwrap_or(String::new());
+            time::strftime("%k:%M:%S", &time::at_utc(time::Timespec::new(seconds, 0))).unwrap_or(String::new()).trim().to_string()
+        } else {
+            time::strftime("%M:%S", &time::at_utc(time::Timespec::new(seconds, 0))).unwrap_or(String::new())
         }
     }
 
@@ -583,14 +1032,82 @@ impl Record
     {
         if let Some(timestr) = dt_time.as_ref()
         {
-            let times: Vec<i32> = timestr.split(":").map(|s| s.parse::<i32>().unwrap()).collect::<Vec<i32>>();
-            if times.len() == 2
+            match Record::parse_time(timestr)
             {
-                return times[1] + (times[0] * 60);
-            } else {
-                return times[2] + (times[1] * 60) + (times[0] * 3600);
-            }        
+                Ok(t) => return t,
+                Err(why) => warn!("Could not parse DeerTier time '{}': {}", timestr, why)
+            }
         }
         0
     }
+
+    /* Parses "42:30", "1:02:03.45", "3723s", "45m" or "1.5h" into whole seconds, dropping fractions */
+    pub fn parse_time(input: &str) -> Result<i32, Box<::std::error::Error>>
+    {
+        let input = input.trim().to_lowercase();
+        let invalid = || -> Box<::std::error::Error> { Box::new(Error::new(ErrorKind::InvalidInput, format!("'{}' is not a valid time", input))) };
+
+        let seconds: f64 = if input.contains(':')
+        {
+            let parts: Vec<&str> = input.split(':').collect();
+            if parts.len() > 3 || parts.iter().any(|p| p.len() == 0)
+            {
+                return Err(invalid());
+            }
+
+            let mut total = 0.0;
+            for (i, part) in parts.iter().enumerate()
+            {
+                /* Only the seconds may have a fraction, and everything but the leading unit must be below 60 */
+                let last = i == parts.len() - 1;
+                let value: f64 = if last { part.parse().map_err(|_| invalid())? } else { part.parse::<u32>().map_err(|_| invalid())? as f64 };
+                if i > 0 && value >= 60.0
+                {
+                    return Err(invalid());
+                }
+                total = total * 60.0 + value;
+            }
+            total
+        } else {
+            let (number, unit) = match input.chars().last()
+            {
+                Some('s') => (&input[..input.len() - 1], 1.0),
+                Some('m') => (&input[..input.len() - 1], 60.0),
+                Some('h') => (&input[..input.len() - 1], 3600.0),
+                _ => (&input[..], 1.0)
+            };
+            number.trim().parse::<f64>().map_err(|_| invalid())? * unit
+        };
+
+        if !seconds.is_finite() || seconds < 0.0 || seconds > ::std::i32::MAX as f64
+        {
+            return Err(invalid());
+        }
+
+        Ok(seconds as i32)
+    }
+
+    /* The rank a run with the given time would take on the board shown by get_top */
+    pub fn rank_for_time(category: Category, realtime: i32) -> Result<i32, Box<::std::error::Error>>
+    {
+        let records = db::query("SELECT COUNT(*) as id FROM records WHERE category=? AND active=1 AND realtime!=0 AND CAST(realtime AS integer)<?", &[&category.to_db(), &realtime])?;
+        if let Some(record) = records.first()
+        {
+            Ok(record["id"].parse::<i32>()? + 1)
+        } else {
+            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the record in the database")))
+        }
+    }
+
+    /* Active runs on the get_top board that are slower than the given time, fastest first */
+    pub fn get_slower(category: Category, realtime: i32, limit: i32) -> Result<Vec<Record>, Box<::std::error::Error>>
+    {
+        let mut slower: Vec<Record> = Vec::new();
+        let records = db::query("SELECT * FROM records WHERE category=? AND active=1 AND realtime!=0 AND CAST(realtime AS integer)>? ORDER BY CAST(realtime AS integer) ASC LIMIT ?", &[&category.to_db(), &realtime, &limit])?;
+        for record in records
+        {
+            slower.push(Record::from_db(&record));
+        }
+        Ok(slower)
+    }
 }
\ No newline at end of file
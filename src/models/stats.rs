@@ -0,0 +1,218 @@
+use models::records::{Category, Region, Record};
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::env;
+use chrono::{Duration, Utc};
+
+pub struct Improvement
+{
+    pub category: Category,
+    pub region: Region,
+    pub date: String,
+    pub realtime: i32,
+    pub gain: i32
+}
+
+// Summary of a single runner, computed from the records table
+pub struct RunnerStats
+{
+    pub categories: i32,
+    pub best_rank: i32,
+    pub average_rank: f64,
+    pub total_time: i32,
+    pub improvements: Vec<Improvement>,
+    pub first_run: String
+}
+
+impl RunnerStats
+{
+    pub fn for_runner(runner_id: i32) -> Result<RunnerStats, Box<::std::error::Error>>
+    {
+        let active = Record::get_records(runner_id)?;
+        let history = Record::get_history(runner_id)?;
+
+        let ranks: Vec<i32> = active.iter().filter_map(|r| r.get_rank().ok()).collect();
+
+        /* Improvements are the gap between each PB and the run it replaced, newest first */
+        let mut improvements: Vec<Improvement> = Vec::new();
+        for r in active.iter()
+        {
+            if let Ok(previous) = r.get_previous_pb()
+            {
+                improvements.push(Improvement { category: r.category, region: r.region, date: r.date.clone(), realtime: r.realtime, gain: previous.realtime - r.realtime });
+            }
+        }
+        improvements.sort_by(|a, b| b.date.cmp(&a.date));
+        improvements.truncate(5);
+
+        Ok(RunnerStats
+        {
+            categories: active.len() as i32,
+            best_rank: ranks.iter().cloned().min().unwrap_or(0),
+            average_rank: if ranks.len() > 0 { ranks.iter().sum::<i32>() as f64 / ranks.len() as f64 } else { 0.0 },
+            total_time: active.iter().map(|r| r.realtime).sum(),
+            improvements: improvements,
+            first_run: history.iter().map(|r| r.date.clone()).filter(|d| d != "").min().unwrap_or(String::new())
+        })
+    }
+}
+
+// Points for a single board, configurable through the environment
+pub struct PointsFormula
+{
+    pub rank_points: f64,
+    pub rank_step: f64,
+    pub time_points: f64,
+    pub time_cutoff: f64
+}
+
+impl PointsFormula
+{
+    pub fn from_env() -> PointsFormula
+    {
+        let var = |name: &str, default: f64| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        PointsFormula
+        {
+            rank_points: var("POINTS_RANK", 50.0),
+            rank_step: var("POINTS_RANK_STEP", 2.0),
+            time_points: var("POINTS_TIME", 50.0),
+            time_cutoff: var("POINTS_TIME_CUTOFF", 25.0)
+        }
+    }
+
+    /* Rank points drop by a fixed step per place; time points drop linearly to zero at the cutoff percentage behind WR */
+    pub fn score(&self, rank: i32, percent_behind: f64) -> f64
+    {
+        let rank_score = (self.rank_points - (rank - 1) as f64 * self.rank_step).max(0.0);
+        let time_score = if self.time_cutoff > 0.0 { (self.time_points * (1.0 - percent_behind / self.time_cutoff)).max(0.0) } else { 0.0 };
+        rank_score + time_score
+    }
+}
+
+pub struct OverallScore
+{
+    pub runner_id: i32,
+    pub points: f64,
+    pub boards: i32
+}
+
+impl OverallScore
+{
+    /* Scores every runner across every category and region board, highest first */
+    pub fn ranking(formula: &PointsFormula) -> Result<Vec<OverallScore>, Box<::std::error::Error>>
+    {
+        let mut scores: HashMap<i32, OverallScore> = HashMap::new();
+        for category in Category::all()
+        {
+            for region in Region::all()
+            {
+                let board = Record::get_board(category, region)?;
+                let wr = match board.first() { Some(r) => r.realtime, None => continue };
+
+                for r in board.iter()
+                {
+                    /* Count ties the same way get_rank does */
+                    let rank = board.iter().filter(|o| o.realtime < r.realtime).count() as i32 + 1;
+                    let percent_behind = (r.realtime - wr) as f64 * 100.0 / wr as f64;
+                    for runner in r.get_runners()
+                    {
+                        let score = scores.entry(runner.id).or_insert(OverallScore { runner_id: runner.id, points: 0.0, boards: 0 });
+                        score.points += formula.score(rank, percent_behind);
+                        score.boards += 1;
+                    }
+                }
+            }
+        }
+
+        let mut ranking: Vec<OverallScore> = scores.into_iter().map(|(_, s)| s).collect();
+        ranking.sort_by(|a, b| b.points.partial_cmp(&a.points).unwrap_or(::std::cmp::Ordering::Equal));
+        Ok(ranking)
+    }
+}
+
+// Distribution of times on a single board
+pub struct CategoryStats
+{
+    pub runners: i32,
+    pub wr: i32,
+    pub quartiles: (i32, i32, i32),
+    pub barriers: Vec<(i32, i32)>,
+    pub new_30: i32,
+    pub new_90: i32,
+    pub histogram: Vec<(i32, i32, i32)>
+}
+
+impl CategoryStats
+{
+    /* Without a region this covers the same runs as get_top, counting each runner once with their best time in any region */
+    pub fn for_board(category: Category, region: Option<Region>) -> Result<CategoryStats, Box<::std::error::Error>>
+    {
+        let board = match region
+        {
+            Some(r) => Record::get_board(category, r)?,
+            None =>
+            {
+                let mut seen: HashSet<i32> = HashSet::new();
+                Record::get_top(category, 0, -1)?.into_iter().filter(|r| seen.insert(r.runner_id)).collect()
+            }
+        };
+
+        let times: Vec<i32> = board.iter().map(|r| r.realtime).collect();
+        if times.len() == 0
+        {
+            return Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find any records for the board")));
+        }
+
+        let today = Utc::now().naive_utc().date();
+        let since = |days: i64| (today - Duration::days(days)).format("%Y-%m-%d").to_string();
+        let first_dates = Record::get_first_dates(category, region)?;
+
+        Ok(CategoryStats
+        {
+            runners: times.len() as i32,
+            wr: times[0],
+            quartiles: (CategoryStats::percentile(&times, 0.25), CategoryStats::percentile(&times, 0.5), CategoryStats::percentile(&times, 0.75)),
+            barriers: CategoryStats::barriers(&times),
+            new_30: first_dates.iter().filter(|d| **d >= since(30)).count() as i32,
+            new_90: first_dates.iter().filter(|d| **d >= since(90)).count() as i32,
+            histogram: CategoryStats::histogram(&times, 8)
+        })
+    }
+
+    /* Nearest-rank percentile of a sorted list */
+    fn percentile(times: &Vec<i32>, p: f64) -> i32
+    {
+        let index = ((times.len() as f64 * p).ceil() as usize).max(1) - 1;
+        times[index.min(times.len() - 1)]
+    }
+
+    /* Round barriers above the WR, with a step that suits the length of the category */
+    fn barriers(times: &Vec<i32>) -> Vec<(i32, i32)>
+    {
+        let wr = times[0];
+        let step = match wr
+        {
+            t if t < 600 => 30,
+            t if t < 3600 => 300,
+            t if t < 7200 => 900,
+            _ => 1800
+        };
+
+        let first = (wr / step + 1) * step;
+        (0..5).map(|i| first + i * step)
+            .map(|b| (b, times.iter().filter(|t| **t < b).count() as i32))
+            .collect()
+    }
+
+    /* Buckets of equal width between the fastest and slowest time, as (from, to, count) */
+    fn histogram(times: &Vec<i32>, buckets: i32) -> Vec<(i32, i32, i32)>
+    {
+        let (min, max) = (times[0], times[times.len() - 1]);
+        let width = (max - min) / buckets + 1;
+        (0..buckets)
+            .map(|i| (min + i * width, min + (i + 1) * width))
+            .take_while(|&(from, _)| from <= max)
+            .map(|(from, to)| (from, to, times.iter().filter(|t| **t >= from && **t < to).count() as i32))
+            .collect()
+    }
+}
\ No newline at end of file
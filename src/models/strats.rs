@@ -0,0 +1,114 @@
+use api::crocomire;
+use std::collections::HashMap;
+use rusqlite;
+use db;
+
+// Local mirror of the crocomi.re strategy catalogue
+pub struct Strat
+{
+    pub id: i32,
+    pub name: String,
+    pub category_name: String,
+    pub area_name: String,
+    pub room_name: String,
+    pub room_id: i32,
+    pub description: String,
+    pub difficulty: i32,
+    pub user_name: String,
+    pub link: String
+}
+
+impl Strat
+{
+    pub fn from_db(record: &HashMap<String, String>) -> Strat
+    {
+        Strat
+        {
+            id: record["id"].parse().unwrap(),
+            name: record["name"].clone(),
+            category_name: record["category_name"].clone(),
+            area_name: record["area_name"].clone(),
+            room_name: record["room_name"].clone(),
+            room_id: record["room_id"].parse().unwrap_or(0),
+            description: record["description"].clone(),
+            difficulty: record["difficulty"].parse().unwrap_or(0),
+            user_name: record["user_name"].clone(),
+            link: record["link"].clone()
+        }
+    }
+
+    pub fn search(search: &str) -> Result<Vec<Strat>, Box<::std::error::Error>>
+    {
+        /* Turn the user input into a prefix match on every word so FTS syntax characters can't break the query */
+        let terms: Vec<String> = search.split_whitespace()
+            .map(|w| w.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+            .filter(|w| w.len() > 0)
+            .map(|w| format!("{}*", w))
+            .collect();
+
+        let mut strats: Vec<Strat> = Vec::new();
+        if terms.len() == 0
+        {
+            return Ok(strats);
+        }
+
+        let records = db::query("SELECT strats.* FROM strats JOIN strats_fts ON strats_fts.docid=strats.id WHERE strats_fts MATCH ? ORDER BY strats.name ASC LIMIT 10", &[&terms.join(" ")])?;
+        for record in records
+        {
+            strats.push(Strat::from_db(&record));
+        }
+        Ok(strats)
+    }
+
+    /* Lists every strat for a crocomi.re category, ordered by area and room as they appear in the game */
+    pub fn get_by_category(category_name: &str) -> Result<Vec<Strat>, Box<::std::error::Error>>
+    {
+        let mut strats: Vec<Strat> = Vec::new();
+        let records = db::query("SELECT * FROM strats WHERE category_name=? COLLATE NOCASE ORDER BY CASE area_name 
+                WHEN 'Ceres Station' THEN 0 WHEN 'Crateria' THEN 1 WHEN 'Brinstar' THEN 2 WHEN 'Norfair' THEN 3 
+                WHEN 'Wrecked Ship' THEN 4 WHEN 'Maridia' THEN 5 WHEN 'Tourian' THEN 6 ELSE 7 END ASC, 
+                CAST(room_id AS integer) ASC, room_name ASC, CAST(difficulty AS integer) ASC", &[&category_name])?;
+        for record in records
+        {
+            strats.push(Strat::from_db(&record));
+        }
+        Ok(strats)
+    }
+
+    pub fn difficulty_str(&self) -> String
+    {
+        match self.difficulty
+        {
+            d if d <= 0 => String::from("☆"),
+            d => "★".repeat(d.min(5) as usize)
+        }
+    }
+
+    pub fn count() -> i32
+    {
+        if let Ok(records) = db::query("SELECT COUNT(*) AS id FROM strats", &[])
+        {
+            if let Some(record) = records.first()
+            {
+                return record["id"].parse().unwrap_or(0);
+            }
+        }
+        0
+    }
+
+    /* Replaces the whole local index with a fresh copy of the catalogue */
+    pub fn replace_all(strategies: &Vec<crocomire::Strategy>) -> rusqlite::Result<()>
+    {
+        db::transaction(|tx|
+        {
+            tx.execute("DELETE FROM strats", &[])?;
+            tx.execute("DELETE FROM strats_fts", &[])?;
+            for s in strategies
+            {
+                tx.execute("INSERT INTO strats (id, name, category_name, area_name, room_name, room_id, description, difficulty, user_name, link) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", &[&s.id, &s.name, &s.category_name, &s.area_name, &s.room_name, &s.room_id.to_string(), &s.description, &s.difficulty.to_string(), &s.user_name, &s.link])?;
+                tx.execute("INSERT INTO strats_fts (docid, name, room_name, area_name, description) VALUES (?, ?, ?, ?, ?)", &[&s.id, &s.name, &s.room_name, &s.area_name, &s.description])?;
+            }
+            Ok(())
+        })
+    }
+}
\ No newline at end of file
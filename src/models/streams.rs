@@ -7,11 +7,19 @@ pub struct Stream
 {
     pub id: i32,
     pub user_id: String,
+    pub user_login: String,
     pub user_name: String,
     pub game_id: String,
     pub game_name: String,
     pub title: String,
-    pub viewers: i32
+    pub viewers: i32,
+    pub peak_viewers: i32,
+    pub started_at: String,
+    /* The go-live announcement, empty when none was posted */
+    pub channel_id: String,
+    pub message_id: String,
+    /* Unix timestamp of the last time the announcement was edited */
+    pub updated: i64
 }
 
 impl Stream
@@ -22,11 +30,17 @@ impl Stream
         {
             id: 0,
             user_id: user_id,
+            user_login: String::new(),
             user_name: user_name,
             game_id: game_id,
             game_name: game_name,
             title: title,
-            viewers: viewers
+            viewers: viewers,
+            peak_viewers: viewers,
+            started_at: String::new(),
+            channel_id: String::new(),
+            message_id: String::new(),
+            updated: 0
         }
     }
 
@@ -36,17 +50,23 @@ impl Stream
         {
             id: record["id"].parse().unwrap(),
             user_id: record["user_id"].clone(),
+            user_login: record["user_login"].clone(),
             user_name: record["user_name"].clone(),
             game_id: record["game_id"].clone(),
             game_name: record["game_name"].clone(),
             title: record["title"].clone(),
-            viewers: record["viewers"].parse().unwrap()
+            viewers: record["viewers"].parse().unwrap(),
+            peak_viewers: record["peak_viewers"].parse().unwrap_or(0),
+            started_at: record["started_at"].clone(),
+            channel_id: record["channel_id"].clone(),
+            message_id: record["message_id"].clone(),
+            updated: record["updated"].parse().unwrap_or(0)
         }
     }
 
-    pub fn from_id(id: i32) -> Result<Stream, Box<::std::error::Error>>
+    pub fn from_user_id(user_id: &String) -> Result<Stream, Box<::std::error::Error>>
     {
-        let records = db::query("SELECT * FROM streams WHERE id=?", &[&id.to_string()])?;
+        let records = db::query("SELECT * FROM streams WHERE user_id=?", &[&user_id.as_str()])?;
         if let Some(record) = records.first()
         {
             Ok(Stream::from_db(record))
@@ -55,14 +75,12 @@ impl Stream
         }
     }
 
-    pub fn from_user_id(user_id: &String) -> Result<Stream, Box<::std::error::Error>>
+    pub fn get_all() -> Vec<Stream>
     {
-        let records = db::query("SELECT * FROM streams WHERE user_id=?", &[&user_id.as_str()])?;
-        if let Some(record) = records.first()
+        match db::query("SELECT * FROM streams", &[])
         {
-            Ok(Stream::from_db(record))
-        } else {
-            Err(Box::new(Error::new(ErrorKind::NotFound, "Could not find the runner in the database")))
+            Ok(records) => records.iter().map(Stream::from_db).collect(),
+            Err(_) => Vec::new()
         }
     }
 
@@ -71,18 +89,33 @@ impl Stream
         let _ = db::exec("UPDATE streams SET modified=0", &[]);
     }
 
-    pub fn delete_unmodified()
+    pub fn delete(&self)
     {
-        let _ = db::exec("DELETE FROM streams WHERE modified=0", &[]);
+        let _ = db::exec("DELETE FROM streams WHERE id=?", &[&self.id.to_string()]);
+    }
+
+    /* Streams that were not seen since clear_modified, i.e. went offline */
+    pub fn get_unmodified() -> Vec<Stream>
+    {
+        match db::query("SELECT * FROM streams WHERE modified=0", &[])
+        {
+            Ok(records) => records.iter().map(Stream::from_db).collect(),
+            Err(_) => Vec::new()
+        }
     }
 
     pub fn save(&mut self)
     {
+        if self.viewers > self.peak_viewers
+        {
+            self.peak_viewers = self.viewers;
+        }
+
         if self.id != 0
         {
-            let _result = db::exec("UPDATE streams SET user_id=?, user_name=?, game_id=?, game_name=?, title=?, viewers=?, modified=1 WHERE id=?", &[&self.user_id, &self.user_name, &self.game_id, &self.game_name, &self.title, &self.viewers.to_string(), &self.id.to_string()]);
+            let _result = db::exec("UPDATE streams SET user_id=?, user_login=?, user_name=?, game_id=?, game_name=?, title=?, viewers=?, peak_viewers=?, started_at=?, channel_id=?, message_id=?, updated=?, modified=1 WHERE id=?", &[&self.user_id, &self.user_login, &self.user_name, &self.game_id, &self.game_name, &self.title, &self.viewers.to_string(), &self.peak_viewers.to_string(), &self.started_at, &self.channel_id, &self.message_id, &self.updated.to_string(), &self.id.to_string()]);
         } else {
-            let _result = db::exec("INSERT INTO streams (user_id, user_name, game_id, game_name, title, viewers, modified) VALUES (?, ?, ?, ?, ?, ?, 1)", &[&self.user_id, &self.user_name, &self.game_id, &self.game_name, &self.title, &self.viewers.to_string()]);
+            let _result = db::exec("INSERT INTO streams (user_id, user_login, user_name, game_id, game_name, title, viewers, peak_viewers, started_at, channel_id, message_id, updated, modified) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)", &[&self.user_id, &self.user_login, &self.user_name, &self.game_id, &self.game_name, &self.title, &self.viewers.to_string(), &self.peak_viewers.to_string(), &self.started_at, &self.channel_id, &self.message_id, &self.updated.to_string()]);
             if let Ok(ids) = db::query("SELECT seq AS id FROM sqlite_sequence WHERE name='streams'", &[])
             {
                 if let Some(id) = ids.first()
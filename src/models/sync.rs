@@ -0,0 +1,65 @@
+use db;
+use chrono::Utc;
+
+// Tracks when each background sync last completed successfully
+pub struct SyncState;
+
+impl SyncState
+{
+    pub fn last_sync(name: &str) -> Option<i64>
+    {
+        if let Ok(records) = db::query("SELECT last_sync FROM sync_state WHERE name=?", &[&name])
+        {
+            if let Some(record) = records.first()
+            {
+                return record["last_sync"].parse().ok();
+            }
+        }
+        None
+    }
+
+    pub fn touch(name: &str)
+    {
+        let _ = db::exec("INSERT OR REPLACE INTO sync_state (name, last_sync) VALUES (?, ?)", &[&name, &Utc::now().timestamp().to_string()]);
+    }
+
+    /* Whether a payload is the same as the one that was last applied */
+    pub fn payload_unchanged(key: &str, hash: &str) -> bool
+    {
+        match db::query("SELECT hash FROM payload_hashes WHERE key=?", &[&key])
+        {
+            Ok(records) => records.first().map(|r| r["hash"] == hash).unwrap_or(false),
+            Err(_) => false
+        }
+    }
+
+    pub fn store_payload(key: &str, hash: &str)
+    {
+        let _ = db::exec("INSERT OR REPLACE INTO payload_hashes (key, hash) VALUES (?, ?)", &[&key, &hash]);
+    }
+
+    pub fn age(name: &str) -> Option<i64>
+    {
+        SyncState::last_sync(name).map(|t| Utc::now().timestamp() - t)
+    }
+
+    pub fn age_str(seconds: i64) -> String
+    {
+        if seconds < 60
+        {
+            format!("{}s", seconds)
+        }
+        else if seconds < 3600
+        {
+            format!("{}m", seconds / 60)
+        }
+        else if seconds < 86400
+        {
+            format!("{}h{:02}m", seconds / 3600, (seconds % 3600) / 60)
+        }
+        else
+        {
+            format!("{}d{}h", seconds / 86400, (seconds % 86400) / 3600)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,48 @@
+use db;
+use rusqlite::types::ToSql;
+use serde_json;
+use chrono::Utc;
+use api::twitch::TwitchUser;
+
+// Twitch users that were looked up before, so they don't have to be fetched on every announcement
+pub struct TwitchUserCache;
+
+impl TwitchUserCache
+{
+    pub fn get_by_logins(logins: &[String], max_age: i64) -> Vec<TwitchUser>
+    {
+        TwitchUserCache::get("login", logins.iter().map(|l| l.to_lowercase()).collect(), max_age)
+    }
+
+    /* SQLite allows at most 999 bound parameters, so the values are looked up 100 at a time */
+    fn get(column: &str, values: Vec<String>, max_age: i64) -> Vec<TwitchUser>
+    {
+        let oldest = (Utc::now().timestamp() - max_age).to_string();
+        let mut users: Vec<TwitchUser> = Vec::new();
+        for chunk in values.chunks(100)
+        {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut args: Vec<&ToSql> = chunk.iter().map(|v| v as &ToSql).collect();
+            args.push(&oldest);
+
+            let q = format!("SELECT data FROM twitch_users WHERE {} IN ({}) AND CAST(updated AS integer)>=CAST(? AS integer)", column, placeholders);
+            if let Ok(records) = db::query(&q, &args)
+            {
+                users.extend(records.iter().filter_map(|r| serde_json::from_str::<TwitchUser>(&r["data"]).ok()));
+            }
+        }
+        users
+    }
+
+    pub fn store(users: &[TwitchUser])
+    {
+        let now = Utc::now().timestamp().to_string();
+        for user in users
+        {
+            if let Ok(data) = serde_json::to_string(user)
+            {
+                let _ = db::exec("INSERT OR REPLACE INTO twitch_users (id, login, display_name, data, updated) VALUES (?, ?, ?, ?, ?)", &[&user.id, &user.login.to_lowercase(), &user.display_name, &data, &now]);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,248 @@
+use serenity::client::Context;
+use serenity::builder::CreateEmbed;
+use serenity::model::{ChannelId, MessageId, UserId, ReactionType, Reaction};
+use typemap::Key;
+use std::collections::HashMap;
+use std::cmp;
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PREVIOUS: &str = "◀";
+const NEXT: &str = "▶";
+
+// Splits list-style replies into pages that fit in a discord message and can be flipped with reactions
+pub struct Paginator
+{
+    header: String,
+    pages: Vec<String>,
+    page: usize,
+    embed: bool,
+    channel_id: ChannelId,
+    author_id: UserId,
+    created: Instant
+}
+
+pub struct Pages;
+impl Key for Pages
+{
+    type Value = HashMap<MessageId, Paginator>;
+}
+
+impl Paginator
+{
+    pub fn new(header: &str, lines: &Vec<String>, per_page: usize) -> Paginator
+    {
+        let mut pages: Vec<String> = Vec::new();
+        let mut page = String::new();
+        let mut count = 0;
+
+        /* Leave room for the header and the page footer */
+        let budget = cmp::max(2000 - cmp::min(header.len() + 32, 1000), 1);
+        for line in lines.iter().flat_map(|l| Paginator::split_line(l, budget - 1))
+        {
+            if count > 0 && (count >= per_page || page.len() + line.len() + 1 > budget)
+            {
+                pages.push(page);
+                page = String::new();
+                count = 0;
+            }
+            page.push_str(&line);
+            page.push('\n');
+            count += 1;
+        }
+        pages.push(page);
+
+        Paginator
+        {
+            header: header.to_string(),
+            pages: pages,
+            page: 0,
+            embed: false,
+            channel_id: ChannelId(0),
+            author_id: UserId(0),
+            created: Instant::now()
+        }
+    }
+
+    /* Cuts a line that would not fit on a page by itself into pieces of at most max bytes */
+    fn split_line(line: &str, max: usize) -> Vec<String>
+    {
+        let mut pieces: Vec<String> = Vec::new();
+        let mut piece = String::new();
+        for c in line.chars()
+        {
+            if piece.len() + c.len_utf8() > max
+            {
+                pieces.push(piece);
+                piece = String::new();
+            }
+            piece.push(c);
+        }
+        pieces.push(piece);
+        pieces
+    }
+
+    /* Renders the pages as embeds instead of plain messages */
+    pub fn embed(mut self, embed: bool) -> Paginator
+    {
+        self.embed = embed;
+        self
+    }
+
+    fn footer(&self) -> String
+    {
+        if self.pages.len() > 1 { format!("Page {}/{}", self.page + 1, self.pages.len()) } else { String::new() }
+    }
+
+    /* Embed titles don't render markdown */
+    fn title(&self) -> String
+    {
+        self.header.replace("**", "")
+    }
+
+    /* Discord rejects an embed with an empty footer, so a single page goes without one */
+    fn page_embed(e: CreateEmbed, title: &str, page: &str, footer: &str) -> CreateEmbed
+    {
+        let e = e.title(title).description(page);
+        if footer != "" { e.footer(|f| f.text(footer)) } else { e }
+    }
+
+    fn content(&self) -> String
+    {
+        if self.pages.len() > 1
+        {
+            format!("{}\n{}*{}*", self.header, self.pages[self.page], self.footer())
+        } else {
+            format!("{}\n{}", self.header, self.pages[self.page])
+        }
+    }
+
+    /* Sends the first page, and registers reaction controls if there is more than one */
+    pub fn send(mut self, ctx: &Context, channel_id: ChannelId, author_id: UserId)
+    {
+        let sent = if self.embed
+        {
+            let (header, page, footer) = (self.title(), self.pages[0].clone(), self.footer());
+            channel_id.send_message(|m| m.embed(|e| Paginator::page_embed(e, &header, &page, &footer)))
+        } else {
+            channel_id.say(self.content())
+        };
+
+        let message = match sent
+        {
+            Ok(m) => m,
+            Err(why) =>
+            {
+                error!("Could not send paginated message: {:?}", why);
+                return;
+            }
+        };
+
+        if self.pages.len() < 2
+        {
+            return;
+        }
+
+        let _ = message.react(ReactionType::Unicode(String::from(PREVIOUS)));
+        let _ = message.react(ReactionType::Unicode(String::from(NEXT)));
+
+        self.channel_id = channel_id;
+        self.author_id = author_id;
+        self.created = Instant::now();
+
+        {
+            let mut data = ctx.data.lock();
+            let pages = data.entry::<Pages>().or_insert_with(HashMap::new);
+            pages.insert(message.id, self);
+        }
+
+        /* Drop the controls once they expire */
+        let data = ctx.data.clone();
+        let message_id = message.id;
+        thread::spawn(move ||
+        {
+            thread::sleep(Paginator::timeout());
+            let expired = data.lock().get_mut::<Pages>().and_then(|pages| pages.remove(&message_id));
+            if let Some(p) = expired
+            {
+                let _ = p.channel_id.delete_reaction(message_id, None, ReactionType::Unicode(String::from(PREVIOUS)));
+                let _ = p.channel_id.delete_reaction(message_id, None, ReactionType::Unicode(String::from(NEXT)));
+            }
+        });
+    }
+
+    pub fn on_reaction(ctx: &Context, reaction: &Reaction)
+    {
+        /* Work out the new page while holding the lock, the discord calls happen after it is released */
+        let update = {
+            let mut data = ctx.data.lock();
+            let p = match data.get_mut::<Pages>().and_then(|pages| pages.get_mut(&reaction.message_id))
+            {
+                Some(p) => p,
+                None => return
+            };
+
+            if reaction.user_id != p.author_id || p.created.elapsed() > Paginator::timeout()
+            {
+                return;
+            }
+
+            let page = match reaction.emoji
+            {
+                ReactionType::Unicode(ref e) if e == PREVIOUS => if p.page > 0 { p.page - 1 } else { p.page },
+                ReactionType::Unicode(ref e) if e == NEXT => if p.page + 1 < p.pages.len() { p.page + 1 } else { p.page },
+                _ => return
+            };
+
+            let changed = page != p.page;
+            p.page = page;
+            (p.channel_id, changed, p.embed, p.title(), p.pages[p.page].clone(), p.footer(), p.content())
+        };
+
+        let (channel_id, changed, embed, header, page, footer, content) = update;
+        let _ = channel_id.delete_reaction(reaction.message_id, Some(reaction.user_id), reaction.emoji.clone());
+        if changed
+        {
+            if embed
+            {
+                let _ = channel_id.edit_message(reaction.message_id, |m| m.embed(|e| Paginator::page_embed(e, &header, &page, &footer)));
+            } else {
+                let _ = channel_id.edit_message(reaction.message_id, |m| m.content(&content));
+            }
+        }
+    }
+
+    fn timeout() -> Duration
+    {
+        Duration::from_secs(env::var("PAGINATOR_TIMEOUT").ok().and_then(|t| t.parse().ok()).unwrap_or(120))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use serenity::builder::CreateEmbed;
+    use super::Paginator;
+
+    fn embed(p: &Paginator) -> CreateEmbed
+    {
+        Paginator::page_embed(CreateEmbed::default(), &p.title(), &p.pages[p.page], &p.footer())
+    }
+
+    #[test]
+    fn single_page_embeds_have_no_footer()
+    {
+        let p = Paginator::new("**Top 10**", &vec![String::from("1. total - 0:41:55")], 10).embed(true);
+        assert!(!embed(&p).0.contains_key("footer"));
+    }
+
+    #[test]
+    fn paged_embeds_show_the_page()
+    {
+        let lines: Vec<String> = (1..21).map(|i| format!("{}. runner", i)).collect();
+        let p = Paginator::new("**Top 20**", &lines, 10).embed(true);
+        assert_eq!(p.footer(), "Page 1/2");
+        assert!(embed(&p).0.contains_key("footer"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Mutex};
+use chrono::Utc;
+use futures::{Future, Stream};
+use hyper;
+use hyper::StatusCode;
+use hyper::server::{Http, Request, Response, Service};
+use api::eventsub::{self, EventSubHeaders, EventSubReply, StreamEvent};
+
+/* How many message ids are remembered to drop redeliveries */
+const SEEN_MESSAGES: usize = 1000;
+
+struct Receiver
+{
+    secret: String,
+    events: Arc<Mutex<mpsc::Sender<StreamEvent>>>,
+    seen: Arc<Mutex<VecDeque<String>>>
+}
+
+impl Receiver
+{
+    fn header(req: &Request, name: &str) -> String
+    {
+        req.headers().get_raw(name).and_then(|r| r.one()).map(|v| String::from_utf8_lossy(v).into_owned()).unwrap_or(String::new())
+    }
+
+    /* Returns false when the message was already delivered before */
+    fn first_delivery(seen: &Mutex<VecDeque<String>>, message_id: &str) -> bool
+    {
+        let mut seen = seen.lock().unwrap();
+        if seen.iter().any(|id| id == message_id)
+        {
+            return false;
+        }
+        seen.push_back(String::from(message_id));
+        if seen.len() > SEEN_MESSAGES
+        {
+            seen.pop_front();
+        }
+        true
+    }
+}
+
+impl Service for Receiver
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, req: Request) -> Self::Future
+    {
+        let headers = EventSubHeaders
+        {
+            message_id: Receiver::header(&req, "Twitch-Eventsub-Message-Id"),
+            message_type: Receiver::header(&req, "Twitch-Eventsub-Message-Type"),
+            timestamp: Receiver::header(&req, "Twitch-Eventsub-Message-Timestamp"),
+            signature: Receiver::header(&req, "Twitch-Eventsub-Message-Signature")
+        };
+        let secret = self.secret.clone();
+        let events = self.events.clone();
+        let seen = self.seen.clone();
+
+        Box::new(req.body().concat2().map(move |chunk|
+        {
+            let body = String::from_utf8_lossy(&chunk).into_owned();
+            match eventsub::handle(&secret, &headers, &body, Utc::now())
+            {
+                EventSubReply::Challenge(challenge) =>
+                {
+                    info!("Verified EventSub subscription callback");
+                    Response::new().with_body(challenge)
+                },
+                EventSubReply::Event(event) =>
+                {
+                    if Receiver::first_delivery(&seen, &headers.message_id)
+                    {
+                        debug!("EventSub event {:?}", event);
+                        let _ = events.lock().unwrap().send(event);
+                    }
+                    Response::new().with_status(StatusCode::NoContent)
+                },
+                EventSubReply::Accepted => Response::new().with_status(StatusCode::NoContent),
+                EventSubReply::Rejected(status, reason) =>
+                {
+                    warn!("Rejected EventSub message {}: {}", headers.message_id, reason);
+                    let status = if status == 403 { StatusCode::Forbidden } else { StatusCode::BadRequest };
+                    Response::new().with_status(status).with_body(reason)
+                }
+            }
+        }))
+    }
+}
+
+/* Serves the webhook callback, verified stream events are sent to the channel. Blocks while the server runs. */
+pub fn serve(listen: &str, secret: String, events: mpsc::Sender<StreamEvent>) -> Result<(), Box<::std::error::Error>>
+{
+    let addr: SocketAddr = listen.parse()?;
+    let events = Arc::new(Mutex::new(events));
+    let seen = Arc::new(Mutex::new(VecDeque::new()));
+
+    let server = Http::new().bind(&addr, move || Ok(Receiver
+    {
+        secret: secret.clone(),
+        events: events.clone(),
+        seen: seen.clone()
+    }))?;
+
+    info!("Listening for EventSub messages on {}", addr);
+    server.run()?;
+    Ok(())
+}
\ No newline at end of file
@@ -1 +1,4 @@
-pub mod streams;
\ No newline at end of file
+pub mod streams;
+pub mod strats;
+pub mod records;
+pub mod eventsub;
\ No newline at end of file
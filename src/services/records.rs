@@ -0,0 +1,254 @@
+use std::thread;
+use std::time::Duration;
+use std::env;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use api::deertier::DeerTier;
+use api::src::{Src, SrcLeaderboard};
+use api::http::{self, ApiError};
+use models::records::{Record, Category, Region};
+use models::changeset::{ChangeSet, Limits, Source};
+use models::sync::SyncState;
+
+lazy_static!
+{
+    /* Change sets held back by the safety limits until a moderator confirms them, keyed by source */
+    static ref PENDING: Mutex<HashMap<&'static str, ChangeSet>> = Mutex::new(HashMap::new());
+}
+
+pub enum SyncOutcome
+{
+    Applied(ChangeSet),
+    Held(ChangeSet, String)
+}
+
+pub fn worker()
+{
+    let interval: u64 = env::var("RECORDS_SYNC_INTERVAL").ok().and_then(|i| i.parse().ok()).unwrap_or(3600);
+
+    loop
+    {
+        for source in [Source::DeerTier, Source::Src].iter()
+        {
+            match sync(*source, false)
+            {
+                Ok(SyncOutcome::Held(_, reason)) => warn!("{} sync is waiting for confirmation: {}", source, reason),
+                Ok(SyncOutcome::Applied(_)) => (),
+                Err(why) => error!("{} sync failed: {:?}", source, why)
+            }
+        }
+
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/* Computes the change set for a source and applies it, unless it passes the safety limits and was not confirmed */
+pub fn sync(source: Source, confirmed: bool) -> Result<SyncOutcome, Box<::std::error::Error>>
+{
+    let mut changes = build(source)?;
+
+    if !confirmed
+    {
+        if let Some(reason) = changes.check(&Limits::from_env())
+        {
+            PENDING.lock().unwrap().insert(source.key(), changes.clone());
+            return Ok(SyncOutcome::Held(changes, reason));
+        }
+    }
+
+    PENDING.lock().unwrap().remove(source.key());
+    changes.apply();
+    Ok(SyncOutcome::Applied(changes))
+}
+
+/* Applies the change set that was held back for the source, if there is one. The source may have changed since,
+   so the change set is computed again and only applied when it still matches the one that was held. */
+pub fn confirm(source: Source) -> Result<Option<SyncOutcome>, Box<::std::error::Error>>
+{
+    let held = match PENDING.lock().unwrap().remove(source.key())
+    {
+        Some(h) => h,
+        None => return Ok(None)
+    };
+
+    let mut changes = match build(source)
+    {
+        Ok(c) => c,
+        Err(why) =>
+        {
+            PENDING.lock().unwrap().insert(source.key(), held);
+            return Err(why);
+        }
+    };
+
+    if changes.lines() != held.lines()
+    {
+        let reason = format!("the changes are no longer the ones held {} minutes ago", (changes.created - held.created) / 60);
+        PENDING.lock().unwrap().insert(source.key(), changes.clone());
+        return Ok(Some(SyncOutcome::Held(changes, reason)));
+    }
+
+    changes.apply();
+    Ok(Some(SyncOutcome::Applied(changes)))
+}
+
+pub fn build(source: Source) -> Result<ChangeSet, Box<::std::error::Error>>
+{
+    match source
+    {
+        Source::DeerTier => build_deertier(),
+        Source::Src => build_src()
+    }
+}
+
+fn build_deertier() -> Result<ChangeSet, Box<::std::error::Error>>
+{
+    let (dt_records, hash) = DeerTier::new().get_records_payload()?;
+    log_cache("DEERTIER");
+    if dt_records.len() == 0
+    {
+        return Err(Box::new(Error::new(ErrorKind::InvalidData, "DeerTier returned no records")));
+    }
+
+    /* Nothing was added or deleted since the list was last applied */
+    if SyncState::payload_unchanged("deertier:records", &hash)
+    {
+        info!("DeerTier records are unchanged, skipping the merge");
+        let mut changes = ChangeSet::build(Source::DeerTier, Vec::new(), Vec::new());
+        changes.unchanged = dt_records.iter().map(|r| r.id.to_string()).collect();
+        return Ok(changes);
+    }
+
+    let incoming: Vec<Record> = dt_records.iter().map(Record::from_deertier_record).collect();
+
+    /* DeerTier lists every submitted run, so anything missing from a complete list has been deleted */
+    let listed: Vec<i32> = dt_records.iter().map(|r| r.id).collect();
+    let removals: Vec<Record> = Record::get_linked_dt()?.into_iter().filter(|r| !listed.contains(&r.dt_id)).collect();
+
+    let mut changes = ChangeSet::build(Source::DeerTier, incoming, removals);
+    changes.payloads.push((String::from("deertier:records"), hash));
+    Ok(changes)
+}
+
+fn build_src() -> Result<ChangeSet, Box<::std::error::Error>>
+{
+    let src = Src::new();
+    let game_id = env::var("SRC_GAME_ID").unwrap_or(String::from("nj1ne1p4"));
+    let fetched = src.get_leaderboard(game_id)?;
+
+    /* Runs on boards that failed to load are left alone instead of being checked one by one */
+    let failed: Vec<(Category, Region)> = fetched.errors.iter().map(|&(ref q, _)| q.mapping()).collect();
+    let mut warnings: Vec<String> = fetched.errors.iter().map(|&(ref q, ref why)| format!("Could not fetch {}: {}", q.label, why)).collect();
+    warnings.extend(fetched.unmapped.iter().map(|q| format!("No category is defined for {}", q.label)));
+
+    /* Boards whose payload is the same as last time only need their runs marked as seen */
+    let mut changed: Vec<SrcLeaderboard> = Vec::new();
+    let mut unchanged: Vec<String> = Vec::new();
+    let mut payloads: Vec<(String, String)> = Vec::new();
+    for lb in fetched.boards
+    {
+        if SyncState::payload_unchanged(&lb.source_url, &lb.payload_hash)
+        {
+            unchanged.extend(lb.runs.iter().map(|r| r.run.id.clone()));
+        } else {
+            payloads.push((lb.source_url.clone(), lb.payload_hash.clone()));
+            changed.push(lb);
+        }
+    }
+    info!("{} SRC leaderboards changed, {} runs on unchanged boards skip the merge", changed.len(), unchanged.len());
+
+    let mut incoming = Src::to_records(changed);
+    if incoming.len() == 0 && unchanged.len() == 0 && failed.len() == 0
+    {
+        return Err(Box::new(Error::new(ErrorKind::InvalidData, "SRC returned no records")));
+    }
+
+    let mut listed: HashSet<String> = incoming.iter().map(|r| r.src_id.clone()).collect();
+    listed.extend(unchanged.iter().cloned());
+
+    /* Leaderboards only show each runner's best verified run, so every other linked run has to be checked on its own.
+       Older runs are checked too, a rejected one would otherwise come back as the PB once the newer run goes. */
+    let mut removals: Vec<Record> = Vec::new();
+    for record in Record::get_linked_src()?.into_iter().filter(|r| !listed.contains(&r.src_id) && !failed.contains(&(r.category, r.region)))
+    {
+        let removed = match src.get_run(&record.src_id)
+        {
+            Ok(run) => run.status.status != "verified",
+            Err(why) => match why.downcast_ref::<ApiError>()
+            {
+                Some(&ApiError::Status(404, _)) => true,
+                _ =>
+                {
+                    warn!("Could not check the status of SRC run {}: {:?}", record.src_id, why);
+                    false
+                }
+            }
+        };
+
+        if removed
+        {
+            info!("SRC run {} has been rejected or deleted", record.src_id);
+            removals.push(record);
+        } else {
+            /* Still verified, keep it marked as seen */
+            incoming.push(record);
+        }
+    }
+    log_cache("SRC");
+
+    let mut changes = ChangeSet::build(Source::Src, incoming, removals);
+    changes.unchanged = unchanged;
+    changes.payloads = payloads;
+    changes.warnings = warnings;
+    Ok(changes)
+}
+
+fn log_cache(service: &str)
+{
+    let (hits, misses) = http::take_cache_stats(service);
+    info!("{} response cache: {} hits, {} misses", service, hits, misses);
+}
+
+/* Builds a change set from local dumps instead of the live APIs: a DeerTier records file,
+   or an SRC leaderboard response (fetched with embed=players) or a directory of them */
+pub fn build_offline(source: Source, path: &Path) -> Result<ChangeSet, Box<::std::error::Error>>
+{
+    let incoming: Vec<Record> = match source
+    {
+        Source::DeerTier =>
+        {
+            let dt_records = DeerTier::parse_records(&read_file(path)?).map_err(|e| format!("{}: {}", path.display(), e))?;
+            dt_records.iter().map(Record::from_deertier_record).collect()
+        },
+        Source::Src =>
+        {
+            let mut files: Vec<PathBuf> = if path.is_dir()
+            {
+                fs::read_dir(path)?.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.extension().map(|e| e == "json").unwrap_or(false)).collect()
+            } else {
+                vec![path.to_path_buf()]
+            };
+            files.sort();
+
+            let mut leaderboards = Vec::new();
+            for file in files
+            {
+                leaderboards.push(Src::parse_leaderboard(&read_file(&file)?).map_err(|e| format!("{}: {}", file.display(), e))?);
+            }
+            Src::to_records(leaderboards)
+        }
+    };
+
+    /* A dump is only a snapshot, so runs missing from it are left alone */
+    Ok(ChangeSet::build(source, incoming, Vec::new()))
+}
+
+fn read_file(path: &Path) -> Result<String, Box<::std::error::Error>>
+{
+    let mut body = String::new();
+    File::open(path)?.read_to_string(&mut body)?;
+    Ok(body)
+}
\ No newline at end of file
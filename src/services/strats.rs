@@ -0,0 +1,43 @@
+use std::thread;
+use std::time::Duration;
+use std::env;
+use api::crocomire;
+use models::strats::Strat;
+use models::sync::SyncState;
+
+pub fn worker()
+{
+    let interval: u64 = env::var("STRAT_REFRESH_INTERVAL").ok().and_then(|i| i.parse().ok()).unwrap_or(21600);
+
+    loop
+    {
+        /* Only hit crocomi.re when the local index is missing or stale */
+        let stale = match SyncState::age("strats")
+        {
+            Some(age) => age >= interval as i64 || Strat::count() == 0,
+            None => true
+        };
+
+        if stale
+        {
+            match crocomire::Strategy::get_all()
+            {
+                Ok(strategies) =>
+                {
+                    match Strat::replace_all(&strategies)
+                    {
+                        Ok(_) =>
+                        {
+                            SyncState::touch("strats");
+                            info!("Refreshed strategy index with {} strats", strategies.len());
+                        },
+                        Err(why) => error!("Could not update strategy index: {:?}", why)
+                    }
+                },
+                Err(why) => warn!("Could not fetch strategies from crocomi.re: {:?}", why)
+            }
+        }
+
+        thread::sleep(Duration::from_secs(600));
+    }
+}
\ No newline at end of file
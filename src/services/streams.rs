@@ -1,12 +1,260 @@
+use std::env;
 use std::thread;
-use serenity::model::ChannelId;
-use models::streams;
-use api::twitch;
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use chrono::{DateTime, Utc};
+use serenity::model::{ChannelId, MessageId};
+use models::streams::Stream;
+use api::twitch::{Twitch, TwitchStream};
+use api::eventsub::StreamEvent;
+use services::eventsub;
 
 pub fn worker()
 {
     /* Wait for discord to connect */
-    thread::sleep_ms(5000);
+    thread::sleep(Duration::from_secs(5));
 
-    /* Do any initialization if needed */
+    let twitch = match Twitch::new(&env::var("TWITCH_CLIENT").unwrap_or(String::new()), &env::var("TWITCH_SECRET").unwrap_or(String::new()))
+    {
+        Some(t) => t,
+        None =>
+        {
+            error!("Could not authenticate with twitch, stream notifications are disabled");
+            return;
+        }
+    };
+
+    match env::var("STREAM_MODE").unwrap_or(String::from("poll")).as_str()
+    {
+        "eventsub" => eventsub_worker(twitch),
+        _ => poll_worker(twitch)
+    }
+}
+
+/* Polls every live stream of the game each STREAM_POLL_INTERVAL seconds */
+fn poll_worker(twitch: Twitch)
+{
+    let interval: u64 = env::var("STREAM_POLL_INTERVAL").ok().and_then(|i| i.parse().ok()).unwrap_or(60);
+    let game_id: i32 = env::var("TWITCH_GAME_ID").ok().and_then(|g| g.parse().ok()).unwrap_or(1229);
+
+    loop
+    {
+        match twitch.get_streams(game_id)
+        {
+            Ok(streams) =>
+            {
+                /* The poller sees every live stream at once, anything it no longer lists went offline */
+                Stream::clear_modified();
+                for s in streams.iter()
+                {
+                    stream_online(s);
+                }
+                for stream in Stream::get_unmodified()
+                {
+                    end_stream(stream);
+                }
+            },
+            Err(why) => error!("Could not get the live streams from twitch: {}", why)
+        }
+
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/* Lets twitch push stream.online/stream.offline for the TWITCH_BROADCASTERS instead of polling */
+fn eventsub_worker(twitch: Twitch)
+{
+    let secret = env::var("EVENTSUB_SECRET").unwrap_or(String::new());
+    let callback = env::var("EVENTSUB_CALLBACK").unwrap_or(String::new());
+    let listen = env::var("EVENTSUB_LISTEN").unwrap_or(String::from("0.0.0.0:8080"));
+    if secret.len() < 10 || callback == ""
+    {
+        error!("EventSub needs EVENTSUB_CALLBACK and an EVENTSUB_SECRET of at least 10 characters, stream notifications are disabled");
+        return;
+    }
+
+    /* The callback has to answer the verification challenge before the subscriptions are created */
+    let (tx, rx) = mpsc::channel();
+    let server_secret = secret.clone();
+    thread::spawn(move ||
+    {
+        if let Err(why) = eventsub::serve(&listen, server_secret, tx)
+        {
+            error!("EventSub receiver stopped: {}", why);
+        }
+    });
+
+    let logins: Vec<String> = env::var("TWITCH_BROADCASTERS").unwrap_or(String::new()).split(',').map(|l| l.trim().to_string()).filter(|l| l != "").collect();
+    match twitch.get_users_by_name(&logins)
+    {
+        Ok(users) =>
+        {
+            for user in users
+            {
+                for event_type in ["stream.online", "stream.offline"].iter()
+                {
+                    if let Err(why) = twitch.subscribe(event_type, &user.id, &callback, &secret)
+                    {
+                        error!("Could not subscribe to {} for {}: {}", event_type, user.login, why);
+                    }
+                }
+            }
+        },
+        Err(why) => error!("Could not look up the twitch broadcasters: {}", why)
+    }
+
+    /* Twitch only pushes online and offline, the viewers and title of live streams are refreshed in between */
+    let interval = Duration::from_secs(env::var("STREAM_POLL_INTERVAL").ok().and_then(|i| i.parse().ok()).unwrap_or(60));
+    let mut refreshed = Instant::now();
+    loop
+    {
+        /* A steady flow of events must not hold off the refresh, so only wait for what is left of the interval */
+        let elapsed = refreshed.elapsed();
+        if elapsed >= interval
+        {
+            refresh_live(&twitch);
+            refreshed = Instant::now();
+            continue;
+        }
+
+        match rx.recv_timeout(interval - elapsed)
+        {
+            Ok(StreamEvent::Online(user_id)) => match twitch.get_streams_by_user(&[user_id.clone()])
+            {
+                Ok(streams) =>
+                {
+                    for s in streams.iter()
+                    {
+                        stream_online(s);
+                    }
+                },
+                Err(why) => error!("Could not get the stream of {}: {}", user_id, why)
+            },
+            Ok(StreamEvent::Offline(user_id)) => stream_offline(&user_id),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break
+        }
+    }
+}
+
+fn refresh_live(twitch: &Twitch)
+{
+    let user_ids: Vec<String> = Stream::get_all().into_iter().map(|s| s.user_id).collect();
+    match twitch.get_streams_by_user(&user_ids)
+    {
+        Ok(streams) =>
+        {
+            for s in streams.iter()
+            {
+                stream_online(s);
+            }
+
+            /* Catches a stream.offline that never arrived, twitch does not redeliver it forever */
+            for user_id in user_ids.iter().filter(|id| !streams.iter().any(|s| &s.user_id == *id))
+            {
+                stream_offline(user_id);
+            }
+        },
+        Err(why) => error!("Could not refresh the live streams: {}", why)
+    }
+}
+
+/* Announces a stream the first time it is seen live, afterwards the announcement follows the title
+   right away and the viewer count every STREAM_UPDATE_INTERVAL seconds */
+pub fn stream_online(live: &TwitchStream)
+{
+    let now = Utc::now().timestamp();
+    match Stream::from_user_id(&live.user_id)
+    {
+        Ok(mut stream) =>
+        {
+            let interval: i64 = env::var("STREAM_UPDATE_INTERVAL").ok().and_then(|i| i.parse().ok()).unwrap_or(300);
+            let edit = stream.title != live.title || (stream.viewers != live.viewer_count && now - stream.updated >= interval);
+
+            stream.title = live.title.clone();
+            stream.viewers = live.viewer_count;
+            if edit
+            {
+                edit_announcement(&stream, &live_message(&stream));
+                stream.updated = now;
+            }
+            stream.save();
+        },
+        Err(_) =>
+        {
+            let mut stream = Stream::new(live.user_id.clone(), live.user_name.clone(), live.game_id.clone(), live.game_name.clone(), live.title.clone(), live.viewer_count);
+            stream.user_login = live.user_login.clone();
+            stream.started_at = live.started_at.clone();
+            announce(&mut stream);
+            stream.updated = now;
+            stream.save();
+        }
+    }
+}
+
+pub fn stream_offline(user_id: &String)
+{
+    if let Ok(stream) = Stream::from_user_id(user_id)
+    {
+        end_stream(stream);
+    }
+}
+
+/* Turns the announcement into a summary of the stream and forgets it */
+fn end_stream(stream: Stream)
+{
+    edit_announcement(&stream, &ended_message(&stream));
+    stream.delete();
+}
+
+fn login(stream: &Stream) -> String
+{
+    if stream.user_login != "" { stream.user_login.clone() } else { stream.user_name.to_lowercase() }
+}
+
+fn live_message(stream: &Stream) -> String
+{
+    format!("**{}** is now live: {}\n{} viewers - <https://twitch.tv/{}>", stream.user_name, stream.title, stream.viewers, login(stream))
+}
+
+fn ended_message(stream: &Stream) -> String
+{
+    let live_for = DateTime::parse_from_rfc3339(&stream.started_at).ok().map(|s| Utc::now().signed_duration_since(s.with_timezone(&Utc)));
+    match live_for
+    {
+        Some(d) => format!("**{}** was live for {}h{:02}m, peak {} viewers: {}\n<https://twitch.tv/{}>", stream.user_name, d.num_hours(), d.num_minutes() % 60, stream.peak_viewers, stream.title, login(stream)),
+        None => format!("**{}** was live, peak {} viewers: {}\n<https://twitch.tv/{}>", stream.user_name, stream.peak_viewers, stream.title, login(stream))
+    }
+}
+
+/* Posts the go-live message to STREAM_CHANNEL and remembers it on the stream */
+fn announce(stream: &mut Stream)
+{
+    let channel: u64 = match env::var("STREAM_CHANNEL").ok().and_then(|c| c.parse().ok())
+    {
+        Some(c) => c,
+        None => return
+    };
+
+    match ChannelId(channel).say(&live_message(stream))
+    {
+        Ok(message) =>
+        {
+            stream.channel_id = channel.to_string();
+            stream.message_id = message.id.0.to_string();
+        },
+        Err(why) => error!("Could not announce the stream of {}: {:?}", stream.user_name, why)
+    }
+}
+
+fn edit_announcement(stream: &Stream, content: &str)
+{
+    let ids = (stream.channel_id.parse::<u64>(), stream.message_id.parse::<u64>());
+    if let (Ok(channel), Ok(message)) = ids
+    {
+        if let Err(why) = ChannelId(channel).edit_message(MessageId(message), |m| m.content(content))
+        {
+            warn!("Could not edit the announcement of {}: {:?}", stream.user_name, why);
+        }
+    }
 }
\ No newline at end of file